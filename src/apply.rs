@@ -0,0 +1,513 @@
+use crate::bundle::{self, BundleFile, HASH_PREFIX};
+use crate::{cache, encoding, manifest, print_colored};
+use diffy::patch_set::{FileOperation, ParseOptions, PatchSet};
+use diffy::{DiffOptions, Patch, PatchFormatter};
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// apply 子命令：把修改后的包或统一 diff 写回工作区
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Options {
+    input: PathBuf,
+    target: PathBuf,
+    base: Option<PathBuf>,
+    yes: bool,
+    dry_run: bool,
+    force: bool,
+}
+
+/// `-Base` 中记录的原文件信息
+#[derive(Default)]
+struct Base {
+    /// 原文件哈希（清单中为完整哈希，旧版包为元信息行中的前缀）
+    hashes: HashMap<String, String>,
+    /// 写入内容被改写过的文件（清单中 `altered` 不为空）
+    altered: HashSet<String>,
+    /// 非 UTF-8 文件的原始编码
+    encodings: HashMap<String, &'static Encoding>,
+    /// 生成时加了行号（`-LineNumbers`），回复中的代码块可能保留行号前缀
+    line_numbers: bool,
+}
+
+/// 磁盘上的当前文件
+struct Current {
+    text: String,
+    /// 非 UTF-8 文件的原始编码，写回时按它编码
+    encoding: Option<&'static Encoding>,
+    /// UTF-16 文件是否带 BOM
+    bom: bool,
+}
+
+/// 一个待写入的文件修改
+struct Change {
+    path: String,
+    target: PathBuf,
+    /// 磁盘上的当前内容；`None` 表示新建
+    old: Option<String>,
+    /// 修改后的内容；`None` 表示删除
+    new: Option<String>,
+    /// 按原文件编码编码后要写入的字节
+    bytes: Vec<u8>,
+}
+
+/// 收集到的修改和无法应用的问题
+#[derive(Default)]
+struct Plan {
+    changes: Vec<Change>,
+    problems: Vec<String>,
+    unchanged: usize,
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(options) = parse_args(args)? else {
+        return Ok(());
+    };
+
+    let text = fs::read_to_string(&options.input)
+        .map_err(|e| format!("无法读取 {}: {}", options.input.display(), e))?;
+    let base = match &options.base {
+        Some(path) => load_base(path)?,
+        None => Base::default(),
+    };
+
+    let parsed = bundle::parse(&text, base.line_numbers);
+    let mut plan = Plan::default();
+    let mut seen = HashSet::new();
+
+    for file in &parsed.files {
+        if !seen.insert(file.path.clone()) {
+            plan.problems
+                .push(format!("{}: 在输入中重复出现", file.path));
+            continue;
+        }
+        // 回复中常丢掉元信息行，以清单的记录为准
+        if file.altered || base.altered.contains(&file.path) {
+            plan.problems.push(format!(
+                "{}: 内容为规范化、截断、大纲、精简或脱敏版本，写回会丢失原内容",
+                file.path
+            ));
+            continue;
+        }
+        let recorded = file.hash.as_ref().or(base.hashes.get(&file.path));
+        plan.add_file(&options, &base, file, recorded);
+    }
+
+    // 没有文件段落和 diff 围栏时，把整个输入当作统一 diff
+    let patches = if parsed.files.is_empty() && parsed.patches.is_empty() {
+        vec![text.clone()]
+    } else {
+        parsed.patches
+    };
+    for patch in &patches {
+        plan.add_patches(&options.target, patch, &base, &mut seen);
+    }
+
+    if plan.changes.is_empty() && plan.problems.is_empty() {
+        if plan.unchanged == 0 {
+            return Err(format!("未在 {} 中找到文件段落或补丁", options.input.display()).into());
+        }
+        print_colored(
+            "green",
+            "✓",
+            &format!("{} 个文件与磁盘一致，无需修改", plan.unchanged),
+        );
+        return Ok(());
+    }
+
+    for change in &plan.changes {
+        print_change(change);
+    }
+    for problem in &plan.problems {
+        print_colored("red", "!", problem);
+    }
+
+    println!();
+    let summary = format!(
+        "修改 {} | 新建 {} | 删除 {} | 未变化 {}",
+        plan.changes
+            .iter()
+            .filter(|c| c.old.is_some() && c.new.is_some())
+            .count(),
+        plan.changes.iter().filter(|c| c.old.is_none()).count(),
+        plan.changes.iter().filter(|c| c.new.is_none()).count(),
+        plan.unchanged
+    );
+    print_colored("blue", "→", &summary);
+
+    if !plan.problems.is_empty() {
+        return Err(format!("{} 个冲突或错误，未写入任何文件", plan.problems.len()).into());
+    }
+    if options.dry_run {
+        print_colored("blue", "→", "预演模式，未写入任何文件");
+        return Ok(());
+    }
+    if !options.yes && !confirm(plan.changes.len())? {
+        print_colored("yellow", "○", "已取消");
+        return Ok(());
+    }
+
+    for change in &plan.changes {
+        match &change.new {
+            Some(_) => {
+                if let Some(parent) = change.target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&change.target, &change.bytes)?;
+            }
+            None => fs::remove_file(&change.target)?,
+        }
+    }
+    print_colored(
+        "green",
+        "✓",
+        &format!("已应用 {} 处修改", plan.changes.len()),
+    );
+    Ok(())
+}
+
+/// 读取 `-Base`：优先使用清单，旧版包退回到元信息行
+fn load_base(path: &Path) -> Result<Base, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+    let mut base = Base::default();
+    match manifest::parse(&text) {
+        Some(manifest) => {
+            base.line_numbers = manifest.config["line_numbers"] == true;
+            for file in manifest.files {
+                if !file.altered.is_empty() {
+                    base.altered.insert(file.path.clone());
+                }
+                if let Some(encoding) = file
+                    .encoding
+                    .and_then(|label| Encoding::for_label(label.as_bytes()))
+                {
+                    base.encodings.insert(file.path.clone(), encoding);
+                }
+                base.hashes.insert(file.path, file.sha256);
+            }
+        }
+        None => {
+            for file in bundle::parse(&text, false).files {
+                if file.altered {
+                    base.altered.insert(file.path.clone());
+                }
+                if let Some(hash) = file.hash {
+                    base.hashes.insert(file.path, hash);
+                }
+            }
+        }
+    }
+    Ok(base)
+}
+
+impl Plan {
+    /// 完整文件：与磁盘内容比较，并按记录的哈希检查磁盘文件是否在生成后被改动
+    ///
+    /// 没有记录哈希时无法确认内容是否为原文，除非 `-Force`，不覆盖已有文件
+    fn add_file(
+        &mut self,
+        options: &Options,
+        base: &Base,
+        file: &BundleFile,
+        recorded: Option<&String>,
+    ) {
+        let path = file.path.as_str();
+        let full_path = match bundle::safe_join(&options.target, path) {
+            Ok(p) => p,
+            Err(reason) => return self.problems.push(reason),
+        };
+        let current = match read_current(path, &full_path, base.encodings.get(path).copied()) {
+            Ok(current) => current,
+            Err(reason) => return self.problems.push(reason),
+        };
+        let old = current.as_ref().map(|c| c.text.as_str());
+        if let Err(reason) = check_hash(path, old, recorded) {
+            return self.problems.push(reason);
+        }
+
+        // 包中的内容总以换行结尾；原文件末尾没有换行时保持原样
+        let mut new = file.content.clone();
+        if let Some(old) = old
+            && !old.ends_with('\n')
+            && new.ends_with('\n')
+        {
+            new.pop();
+        }
+
+        if recorded.is_none() && old.is_some_and(|old| old != new) && !options.force {
+            return self.problems.push(format!(
+                "{}: 缺少原文件哈希（元信息行或 -Base 清单），无法确认内容未被改写；确认无误后用 -Force 写回",
+                path
+            ));
+        }
+        self.push(path, full_path, current, Some(new));
+    }
+
+    /// 统一 diff：逐个文件应用到磁盘内容上，上下文对不上时记为冲突
+    fn add_patches(&mut self, target: &Path, text: &str, base: &Base, seen: &mut HashSet<String>) {
+        // git 格式需要 `diff --git` 头，普通统一 diff 按 unidiff 解析
+        let format = if text.contains("diff --git ") {
+            ParseOptions::gitdiff()
+        } else {
+            ParseOptions::unidiff()
+        };
+        for file_patch in PatchSet::parse(text, format) {
+            let file_patch = match file_patch {
+                Ok(p) => p,
+                Err(e) => return self.problems.push(format!("无法解析补丁: {}", e)),
+            };
+            let Some(patch) = file_patch.patch().as_text() else {
+                self.problems.push("不支持二进制补丁".to_string());
+                continue;
+            };
+
+            let (path, creates, deletes) = match file_patch.operation() {
+                FileOperation::Create(p) => (strip_ab(target, p), true, false),
+                FileOperation::Delete(p) => (strip_ab(target, p), false, true),
+                FileOperation::Modify { original, modified } => {
+                    let original = strip_ab(target, original);
+                    if original != strip_ab(target, modified) {
+                        self.problems
+                            .push(format!("{}: 不支持重命名或复制补丁", original));
+                        continue;
+                    }
+                    (original, false, false)
+                }
+                FileOperation::Rename { from, .. } | FileOperation::Copy { from, .. } => {
+                    self.problems
+                        .push(format!("{}: 不支持重命名或复制补丁", from));
+                    continue;
+                }
+            };
+            if !seen.insert(path.clone()) {
+                self.problems.push(format!("{}: 在输入中重复出现", path));
+                continue;
+            }
+
+            let full_path = match bundle::safe_join(target, &path) {
+                Ok(p) => p,
+                Err(reason) => {
+                    self.problems.push(reason);
+                    continue;
+                }
+            };
+            let current = match read_current(&path, &full_path, base.encodings.get(&path).copied())
+            {
+                Ok(current) => current,
+                Err(reason) => {
+                    self.problems.push(reason);
+                    continue;
+                }
+            };
+            let old = current.as_ref().map(|c| c.text.as_str());
+            if let Err(reason) = check_hash(&path, old, base.hashes.get(&path)) {
+                self.problems.push(reason);
+                continue;
+            }
+            if creates && old.is_some() {
+                self.problems
+                    .push(format!("{}: 补丁要新建的文件已存在", path));
+                continue;
+            }
+            if !creates && old.is_none() {
+                self.problems
+                    .push(format!("{}: 补丁要修改的文件不存在", path));
+                continue;
+            }
+
+            match diffy::apply(old.unwrap_or_default(), patch) {
+                Ok(_) if deletes => self.push(&path, full_path, current, None),
+                Ok(new) => self.push(&path, full_path, current, Some(new)),
+                Err(e) => self
+                    .problems
+                    .push(format!("{}: 补丁无法应用（{}）", path, e)),
+            }
+        }
+    }
+
+    /// 记录一处修改；新内容按原文件的编码写回，无法编码时记为问题
+    fn push(&mut self, path: &str, target: PathBuf, current: Option<Current>, new: Option<String>) {
+        let old = current.as_ref().map(|c| c.text.clone());
+        if old == new {
+            self.unchanged += 1;
+            return;
+        }
+        let (encoding, bom) = current.map_or((None, false), |c| (c.encoding, c.bom));
+        let bytes = match new.as_deref().map(|text| encode(text, encoding, bom)) {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(reason)) => return self.problems.push(format!("{}: {}", path, reason)),
+            None => Vec::new(),
+        };
+        self.changes.push(Change {
+            path: path.to_string(),
+            target,
+            old,
+            new,
+            bytes,
+        });
+    }
+}
+
+/// 读取并解码磁盘上的文件；不存在时为 `None`
+///
+/// `hint` 为清单中记录的原始编码，未记录时只接受能确定的编码
+fn read_current(
+    path: &str,
+    full_path: &Path,
+    hint: Option<&'static Encoding>,
+) -> Result<Option<Current>, String> {
+    let bytes = match fs::read(full_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: 无法读取（{}）", path, e)),
+    };
+    let bom = Encoding::for_bom(&bytes).is_some_and(|(encoding, _)| encoding != UTF_8);
+    let decoded = encoding::decode(bytes, hint.as_slice())
+        .ok_or_else(|| format!("{}: 无法识别磁盘上文件的编码", path))?;
+    Ok(Some(Current {
+        text: decoded.text,
+        encoding: decoded
+            .encoding
+            .and_then(|name| Encoding::for_label(name.as_bytes())),
+        bom,
+    }))
+}
+
+/// 按原文件的编码编码新内容；UTF-16 由 encoding_rs 之外手动编码
+fn encode(text: &str, encoding: Option<&'static Encoding>, bom: bool) -> Result<Vec<u8>, String> {
+    let Some(encoding) = encoding else {
+        return Ok(text.as_bytes().to_vec());
+    };
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let bom = bom.then_some(0xfeff);
+        let units = bom.into_iter().chain(text.encode_utf16());
+        return Ok(if encoding == UTF_16LE {
+            units.flat_map(u16::to_le_bytes).collect()
+        } else {
+            units.flat_map(u16::to_be_bytes).collect()
+        });
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        return Err(format!("新内容含有 {} 无法表示的字符", encoding.name()));
+    }
+    Ok(bytes.into_owned())
+}
+
+/// 包中记录了原文件哈希时，磁盘内容必须仍与之一致
+fn check_hash(path: &str, current: Option<&str>, recorded: Option<&String>) -> Result<(), String> {
+    let Some(recorded) = recorded else {
+        return Ok(());
+    };
+    match current {
+        None => Err(format!("{}: 生成后已从磁盘删除", path)),
+        Some(content) if !cache::hash(content).starts_with(recorded.as_str()) => {
+            Err(format!("{}: 生成后磁盘上的文件已被修改", path))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// 去掉 git diff 的 `a/`、`b/` 前缀；目录中确实存在同名路径时保留
+fn strip_ab(target: &Path, path: &str) -> String {
+    match path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")) {
+        Some(stripped) if !target.join(path).exists() => stripped.to_string(),
+        _ => path.to_string(),
+    }
+}
+
+fn print_change(change: &Change) {
+    let (color, symbol, label) = match (&change.old, &change.new) {
+        (None, _) => ("green", "+", "新建"),
+        (_, None) => ("red", "-", "删除"),
+        _ => ("yellow", "~", "修改"),
+    };
+    println!();
+    print_colored(color, symbol, &format!("{}（{}）", change.path, label));
+
+    let old = change.old.as_deref().unwrap_or_default();
+    let new = change.new.as_deref().unwrap_or_default();
+    let patch: Patch<'_, str> = DiffOptions::new()
+        .set_original_filename(format!("a/{}", change.path))
+        .set_modified_filename(format!("b/{}", change.path))
+        .create_patch(old, new);
+    print!("{}", PatchFormatter::new().with_color().fmt_patch(&patch));
+}
+
+fn confirm(count: usize) -> io::Result<bool> {
+    print!("\x1b[33m?\x1b[0m 应用以上 {} 处修改？[y/N]: ", count);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, Box<dyn std::error::Error>> {
+    let mut input = None;
+    let mut options = Options {
+        input: PathBuf::new(),
+        target: PathBuf::from("."),
+        base: None,
+        yes: false,
+        dry_run: false,
+        force: false,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return Ok(None);
+            }
+            "-Path" => {
+                i += 1;
+                options.target = PathBuf::from(args.get(i).ok_or("缺少 -Path 的值")?);
+            }
+            "-Base" => {
+                i += 1;
+                options.base = Some(PathBuf::from(args.get(i).ok_or("缺少 -Base 的值")?));
+            }
+            "-Yes" | "--yes" => options.yes = true,
+            "-DryRun" => options.dry_run = true,
+            "-Force" => options.force = true,
+            arg if !arg.starts_with('-') && input.is_none() => input = Some(PathBuf::from(arg)),
+            arg => return Err(format!("未知参数: {}", arg).into()),
+        }
+        i += 1;
+    }
+
+    options.input = input.ok_or("缺少要应用的文件，用法见 apply -h")?;
+    Ok(Some(options))
+}
+
+fn print_help() {
+    println!(
+        r#"
+用法:
+  code_collector apply <文件> [选项]
+
+把修改后的 Markdown 包（### 路径 + 代码块）或统一 diff 写回目录。
+先显示彩色差异，确认后才写入。
+
+选项:
+  -Path <目录>         要修改的目录（默认: 当前目录）
+  -Base <文件>         生成时的原始包，用于检查磁盘文件是否已被改动
+  -Yes, --yes          不询问，直接应用
+  -DryRun              只显示差异，不写入
+  -Force               覆盖缺少原文件哈希（回复中无 🔑 且 -Base 中无记录）的文件
+  -h, --help           显示帮助信息
+
+包中每个文件记录了原文件哈希（🔑，前 {} 位），磁盘文件在生成后被改动时报告冲突；
+缺少哈希的已有文件默认不覆盖。规范化、截断、大纲、精简或脱敏过的文件
+（按元信息行或 -Base 清单判断）不会写回。非 UTF-8 文件按原编码写回。
+存在任何冲突时不写入任何文件。
+"#,
+        HASH_PREFIX
+    );
+}
@@ -0,0 +1,152 @@
+use crate::glob_match;
+use std::fs;
+use std::path::Path;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// .gitattributes：linguist 覆盖与 export-ignore
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Clone, PartialEq)]
+enum Value {
+    Set,
+    Unset,
+    Text(String),
+}
+
+impl Value {
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Set => Some(true),
+            Self::Unset => Some(false),
+            Self::Text(text) if text == "true" => Some(true),
+            Self::Text(text) if text == "false" => Some(false),
+            Self::Text(_) => None,
+        }
+    }
+}
+
+struct Rule {
+    base: String, // 所在 .gitattributes 相对目标目录的目录，根目录为空
+    pattern: String,
+    /// 属性名与取值；`None` 表示 `!attr`（恢复为未指定）
+    attrs: Vec<(String, Option<Value>)>,
+}
+
+/// 单个路径生效的属性
+#[derive(Default)]
+pub struct FileAttributes {
+    pub language: Option<String>,
+    /// `Some(false)` 表示明确声明不是生成文件
+    pub generated: Option<bool>,
+    pub vendored: Option<bool>,
+    pub export_ignore: bool,
+}
+
+/// 扫描过程中遇到的 `.gitattributes` 规则
+#[derive(Default)]
+pub struct Attributes {
+    rules: Vec<Rule>,
+    /// `.git/info/attributes`，优先级最高
+    info: Vec<Rule>,
+}
+
+impl Attributes {
+    /// 读取目录下的 `.gitattributes`；在根目录时还读取 `.git/info/attributes`
+    ///
+    /// 目录按深度优先顺序进入，因此较深目录的规则排在后面，匹配时覆盖较浅的规则
+    pub fn load_dir(&mut self, dir: &Path, relative_dir: &str) {
+        if let Ok(text) = fs::read_to_string(dir.join(".gitattributes")) {
+            self.rules.extend(parse(&text, relative_dir));
+        }
+        if relative_dir.is_empty()
+            && let Ok(text) = fs::read_to_string(dir.join(".git/info/attributes"))
+        {
+            self.info = parse(&text, "");
+        }
+    }
+
+    pub fn lookup(&self, relative_path: &str) -> FileAttributes {
+        let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        let mut language = None;
+        let mut generated = None;
+        let mut vendored = None;
+        let mut export_ignore = None;
+
+        for rule in self.rules.iter().chain(&self.info) {
+            let Some(path) = strip_base(relative_path, &rule.base) else {
+                continue;
+            };
+            // 含 `/` 的模式相对 .gitattributes 所在目录匹配，否则匹配文件名
+            let matched = match rule.pattern.strip_prefix('/') {
+                Some(anchored) => glob_match(anchored, path),
+                None if rule.pattern.contains('/') => glob_match(&rule.pattern, path),
+                None => glob_match(&rule.pattern, file_name),
+            };
+            if !matched {
+                continue;
+            }
+            for (name, value) in &rule.attrs {
+                let slot = match name.as_str() {
+                    "linguist-language" => &mut language,
+                    "linguist-generated" => &mut generated,
+                    "linguist-vendored" => &mut vendored,
+                    "export-ignore" => &mut export_ignore,
+                    _ => continue,
+                };
+                *slot = value.clone();
+            }
+        }
+
+        FileAttributes {
+            language: match language {
+                Some(Value::Text(name)) => Some(name),
+                _ => None,
+            },
+            generated: generated.as_ref().and_then(Value::as_bool),
+            vendored: vendored.as_ref().and_then(Value::as_bool),
+            export_ignore: export_ignore.is_some_and(|v| v.as_bool() == Some(true)),
+        }
+    }
+}
+
+fn strip_base<'a>(relative_path: &'a str, base: &str) -> Option<&'a str> {
+    if base.is_empty() {
+        return Some(relative_path);
+    }
+    relative_path.strip_prefix(base)?.strip_prefix('/')
+}
+
+/// 解析 gitattributes：每行一个模式加若干属性；忽略注释、宏定义和带引号的模式
+fn parse(text: &str, base: &str) -> Vec<Rule> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('"') {
+                return None;
+            }
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?;
+            if pattern.starts_with("[attr]") {
+                return None;
+            }
+            let attrs = fields
+                .map(|field| {
+                    if let Some(name) = field.strip_prefix('-') {
+                        (name.to_string(), Some(Value::Unset))
+                    } else if let Some(name) = field.strip_prefix('!') {
+                        (name.to_string(), None)
+                    } else if let Some((name, value)) = field.split_once('=') {
+                        (name.to_string(), Some(Value::Text(value.to_string())))
+                    } else {
+                        (field.to_string(), Some(Value::Set))
+                    }
+                })
+                .collect();
+            Some(Rule {
+                base: base.to_string(),
+                pattern: pattern.to_string(),
+                attrs,
+            })
+        })
+        .collect()
+}
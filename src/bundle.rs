@@ -0,0 +1,304 @@
+use crate::{cache, manifest};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Markdown 包解析
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 元信息行中记录的原文件 SHA-256 前缀长度（十六进制字符数）
+pub const HASH_PREFIX: usize = 16;
+
+/// 包中的一个文件：`### 路径` 标题后紧跟的围栏代码块
+pub struct BundleFile {
+    pub path: String,
+    pub content: String,
+    /// 生成时原文件的哈希前缀（元信息行中的 `🔑`）
+    pub hash: Option<String>,
+    /// 内容是否被规范化、截断、大纲、精简或脱敏改写过，写回后与原文件不同
+    pub altered: bool,
+}
+
+/// 解析结果：完整文件，以及语言标记为 `diff`/`patch` 的补丁块
+#[derive(Default)]
+pub struct Bundle {
+    pub files: Vec<BundleFile>,
+    pub patches: Vec<String>,
+}
+
+/// 元信息行中表示内容已被改写的标记
+const ALTERED_MARKERS: &[&str] = &["已规范化", "已截断", "仅大纲", "已精简", "已脱敏"];
+
+/// 元信息行中表示代码块带行号前缀的标记
+const NUMBERED_MARKER: &str = "带行号";
+
+/// 解析 `write_files` 写出的文件段落
+///
+/// 标题与围栏之间只允许空行和 `>` 元信息行，因此统计表等其他三级标题会被忽略；
+/// 围栏长度可变，闭合围栏须由同一字符组成且不短于开启围栏。
+/// 不带标题的 diff 围栏（常见于模型回复）作为补丁收集。
+///
+/// 行号前缀只在 `line_numbers` 为真（调用方已知，如 `-Base` 清单）、内嵌清单记录了
+/// `line_numbers` 或元信息行带有行号标记时去掉，本身以数字开头的内容不会被误改。
+pub fn parse(text: &str, line_numbers: bool) -> Bundle {
+    let manifest = manifest::parse(text);
+    let line_numbers = line_numbers
+        || manifest
+            .as_ref()
+            .is_some_and(|m| m.config["line_numbers"] == true);
+    // 元信息行丢失时用清单中的哈希判断末尾换行
+    let recorded: HashMap<&str, &str> = manifest
+        .iter()
+        .flat_map(|m| &m.files)
+        .map(|f| (f.path.as_str(), f.sha256.as_str()))
+        .collect();
+    // 只按 `\n` 切分，保留 CRLF 文件中的 `\r`
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut bundle = Bundle::default();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some(path) = lines[i].strip_prefix("### ").map(str::trim) else {
+            if let Some((lang, content)) = read_block(&lines, &mut i) {
+                if is_diff_lang(&lang) {
+                    bundle.patches.push(content);
+                }
+            } else {
+                i += 1;
+            }
+            continue;
+        };
+        let path = path.trim_matches('`').to_string();
+        i += 1;
+
+        let mut altered = false;
+        let mut numbered = line_numbers;
+        let mut hash = None;
+        while i < lines.len() && (lines[i].trim().is_empty() || lines[i].starts_with('>')) {
+            altered |= ALTERED_MARKERS.iter().any(|m| lines[i].contains(m));
+            numbered |= lines[i].starts_with('>') && lines[i].contains(NUMBERED_MARKER);
+            if let Some((_, rest)) = lines[i].split_once("🔑 ") {
+                let digest: String = rest.chars().take_while(char::is_ascii_hexdigit).collect();
+                hash = Some(digest).filter(|d| !d.is_empty());
+            }
+            i += 1;
+        }
+
+        let Some((lang, content)) = read_block(&lines, &mut i) else {
+            continue;
+        };
+        // 被收集的 .diff/.patch 文件本身是完整文件，不是要应用的补丁
+        let is_diff_file = path.ends_with(".diff") || path.ends_with(".patch");
+        if is_diff_lang(&lang) && !is_diff_file {
+            bundle.patches.push(content);
+        } else {
+            let content = if numbered {
+                strip_line_numbers(&content).unwrap_or(content)
+            } else {
+                content
+            };
+            let digest = hash.as_deref().or(recorded.get(path.as_str()).copied());
+            let content = restore_final_newline(content, digest);
+            bundle.files.push(BundleFile {
+                path,
+                content,
+                hash,
+                altered,
+            });
+        }
+    }
+
+    bundle
+}
+
+/// 围栏内容总以换行结束；去掉末尾换行后才与记录的哈希（前缀）一致时，说明原文件没有末尾换行
+fn restore_final_newline(mut content: String, digest: Option<&str>) -> String {
+    let Some(digest) = digest.filter(|d| !d.is_empty()) else {
+        return content;
+    };
+    let matches = |text: &str| cache::hash(text).starts_with(digest);
+    if let Some(trimmed) = content.strip_suffix('\n')
+        && !matches(&content)
+        && matches(trimmed)
+    {
+        content.pop();
+    }
+    content
+}
+
+fn is_diff_lang(lang: &str) -> bool {
+    matches!(lang, "diff" | "patch" | "udiff")
+}
+
+/// 从 `lines[*i]` 的开启围栏读到闭合围栏，返回（语言标记, 内容）并移动到闭合围栏之后
+fn read_block(lines: &[&str], i: &mut usize) -> Option<(String, String)> {
+    let (fence_char, fence_len) = opening_fence(lines.get(*i)?)?;
+    let lang = lines[*i]
+        .trim()
+        .trim_start_matches(fence_char)
+        .trim()
+        .to_lowercase();
+    *i += 1;
+
+    let start = *i;
+    while *i < lines.len() && !is_closing_fence(lines[*i], fence_char, fence_len) {
+        *i += 1;
+    }
+    let mut content = lines[start..*i].join("\n");
+    if *i > start {
+        content.push('\n');
+    }
+    *i += 1;
+
+    Some((lang, content))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 行号
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 行号与代码之间的分隔符
+const LINE_NUMBER_SEPARATOR: &str = " │";
+
+/// 给每行加上右对齐的行号前缀（`-LineNumbers`），如 `  7 │ code`；空行不留行尾空格
+///
+/// `gap` 为截断时省略标记所在的行和省略的行数：标记行不编号，之后的行号跳过省略的部分
+pub fn number_lines(content: &str, gap: Option<(usize, usize)>) -> String {
+    let lines = content.lines().count();
+    let last = match gap {
+        Some((_, omitted)) => lines - 1 + omitted,
+        None => lines,
+    };
+    let width = last.to_string().len();
+    let mut output = String::with_capacity(content.len() + content.len() / 4);
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        let number = match gap {
+            Some((at, _)) if i == at => String::new(),
+            Some((at, omitted)) if i > at => (i + omitted).to_string(),
+            _ => (i + 1).to_string(),
+        };
+        output.push_str(&format!("{:>width$}{}", number, LINE_NUMBER_SEPARATOR));
+        if !line.trim_end_matches(['\r', '\n']).is_empty() {
+            output.push(' ');
+        }
+        output.push_str(line);
+    }
+    output
+}
+
+/// 每一行都带行号前缀时去掉前缀；模型改写时增删的行可能打乱编号，因此不要求连续
+fn strip_line_numbers(content: &str) -> Option<String> {
+    if content.is_empty() {
+        return None;
+    }
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let rest = line.trim_start_matches(' ');
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            // 截断标记行只有空白的行号栏
+            let rest = if digits == 0 && line.starts_with(' ') {
+                rest.strip_prefix(LINE_NUMBER_SEPARATOR.trim_start())?
+            } else if digits > 0 {
+                rest[digits..].strip_prefix(LINE_NUMBER_SEPARATOR)?
+            } else {
+                return None;
+            };
+            Some(rest.strip_prefix(' ').unwrap_or(rest))
+        })
+        .collect()
+}
+
+/// 开启围栏：至少 3 个反引号或波浪线，后面可跟语言标记
+fn opening_fence(line: &str) -> Option<(char, usize)> {
+    let line = line.trim_start();
+    let fence_char = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = line.chars().take_while(|&c| c == fence_char).count();
+    (len >= 3).then_some((fence_char, len))
+}
+
+fn is_closing_fence(line: &str, fence_char: char, fence_len: usize) -> bool {
+    let line = line.trim();
+    line.chars().count() >= fence_len && line.chars().all(|c| c == fence_char)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 路径安全
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 把包中的相对路径解析到 `base` 之下；绝对路径、盘符和 `..` 一律拒绝
+pub fn safe_join(base: &Path, relative_path: &str) -> Result<PathBuf, String> {
+    let normalized = relative_path.replace('\\', "/");
+    let has_drive = normalized.as_bytes().get(1) == Some(&b':');
+    if normalized.is_empty() || normalized.starts_with('/') || has_drive {
+        return Err(format!("不允许绝对路径: {}", relative_path));
+    }
+
+    let mut target = base.to_path_buf();
+    for component in Path::new(&normalized).components() {
+        match component {
+            Component::Normal(part) => target.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return Err(format!("不允许包含 `..`: {}", relative_path)),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("不允许绝对路径: {}", relative_path));
+            }
+        }
+    }
+    if target == base {
+        return Err(format!("路径为空: {}", relative_path));
+    }
+
+    // 已存在的文件或上级目录可能是指向外部（或悬空）的符号链接
+    let mut existing = target.as_path();
+    while existing != base
+        && existing.symlink_metadata().is_err()
+        && let Some(parent) = existing.parent()
+    {
+        existing = parent;
+    }
+    if existing != base && existing.symlink_metadata().is_ok() {
+        let inside = match (existing.canonicalize(), base.canonicalize()) {
+            (Ok(resolved), Ok(root)) => resolved.starts_with(&root),
+            _ => false,
+        };
+        if !inside {
+            return Err(format!("路径经符号链接指向目标目录之外: {}", relative_path));
+        }
+    }
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(path: &str, content: &str, meta: &str) -> String {
+        format!(
+            "### {}\n\n> {}🔑 {}\n\n```\n{}\n```\n",
+            path,
+            meta,
+            &cache::hash(content)[..HASH_PREFIX],
+            content.trim_end_matches('\n')
+        )
+    }
+
+    #[test]
+    fn missing_final_newline_is_restored_from_the_hash() {
+        let files = parse(&bundle("a.txt", "no newline", ""), false).files;
+        assert_eq!(files[0].content, "no newline");
+        let files = parse(&bundle("b.txt", "newline\n", ""), false).files;
+        assert_eq!(files[0].content, "newline\n");
+    }
+
+    #[test]
+    fn line_numbers_are_stripped_only_when_marked() {
+        let content = "1 │ looks numbered\n";
+        let files = parse(&bundle("a.txt", content, ""), false).files;
+        assert_eq!(files[0].content, content);
+        let numbered = number_lines("code\n", None);
+        let files = parse(&bundle("a.txt", &numbered, "🔢 带行号 | "), false).files;
+        assert_eq!(files[0].content, "code\n");
+    }
+}
@@ -0,0 +1,325 @@
+use crate::estimate_tokens;
+use crate::generated::{self, Kind};
+use crate::loc::{self, LineCounts};
+use encoding_rs::Encoding;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 增量缓存（磁盘）
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 缓存文件格式版本；格式或统计口径变化时递增，旧缓存自动作废
+const FORMAT: &str = "code-collector-cache v6";
+
+/// 单个文件的统计结果
+#[derive(Clone)]
+pub struct FileInfo {
+    pub hash: String,
+    /// 识别出的语言；可能取决于 shebang 或模式行，因此随内容一起缓存
+    pub language: String,
+    pub line_count: usize,
+    pub lines: LineCounts,
+    pub tokens: usize,
+    /// 按文件名、头部标记或行长度判断出的生成/压缩文件
+    pub generated: Option<Kind>,
+    /// 原始编码；UTF-8 为 `None`
+    pub encoding: Option<&'static str>,
+}
+
+impl FileInfo {
+    fn compute(
+        relative_path: &str,
+        content: &str,
+        language: &str,
+        encoding: Option<&'static str>,
+        hash: String,
+    ) -> Self {
+        Self {
+            encoding,
+            generated: generated::detect(relative_path, content, language),
+            hash,
+            language: language.to_string(),
+            line_count: content.lines().count(),
+            lines: loc::count_lines(content, language),
+            tokens: estimate_tokens(content),
+        }
+    }
+}
+
+struct Entry {
+    size: u64,
+    modified: Option<u128>,
+    info: FileInfo,
+}
+
+/// 按路径保存文件的大小、修改时间、哈希与统计结果，未变化的文件无需重新统计
+#[derive(Default)]
+pub struct DiskCache {
+    /// 缓存文件路径；为 `None` 时不读写磁盘（`-NoCache`）
+    path: Option<PathBuf>,
+    root: String,
+    entries: HashMap<String, Entry>,
+    seen: HashSet<String>,
+    dirty: bool,
+}
+
+impl DiskCache {
+    /// 打开 `root` 对应的缓存；缓存不存在或已损坏时从空缓存开始
+    pub fn open(root: &Path) -> Self {
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let root = root.to_string_lossy().to_string();
+        let Some(path) = cache_dir().map(|dir| dir.join(format!("{}.tsv", &hash(&root)[..16])))
+        else {
+            return Self::default();
+        };
+
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| parse(&text, &root))
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            root,
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// 大小和修改时间都未变化时直接返回缓存的统计结果（含识别出的语言）
+    ///
+    /// 非 UTF-8 文件的解码结果取决于本次的 `-Encodings`，不走这条捷径，
+    /// 由 `resolve` 按重新解码的内容比较哈希
+    pub fn lookup(&mut self, relative_path: &str, metadata: &fs::Metadata) -> Option<FileInfo> {
+        let modified = modified_nanos(metadata)?;
+        let entry = self.entries.get(relative_path)?;
+        if entry.size != metadata.len()
+            || entry.modified != Some(modified)
+            || entry.info.encoding.is_some()
+        {
+            return None;
+        }
+        self.seen.insert(relative_path.to_string());
+        Some(entry.info.clone())
+    }
+
+    /// 修改时间变了但内容哈希相同（如 `touch`、切换分支）时复用统计结果，否则重新统计
+    ///
+    /// `content` 为解码后的内容，`encoding` 为原始编码
+    pub fn resolve(
+        &mut self,
+        relative_path: &str,
+        metadata: &fs::Metadata,
+        language: &str,
+        content: &str,
+        encoding: Option<&'static str>,
+    ) -> FileInfo {
+        let digest = hash(content);
+        let info = match self.entries.get(relative_path) {
+            Some(entry)
+                if entry.info.hash == digest
+                    && entry.info.language == language
+                    && entry.info.encoding == encoding =>
+            {
+                entry.info.clone()
+            }
+            _ => FileInfo::compute(relative_path, content, language, encoding, digest),
+        };
+
+        self.seen.insert(relative_path.to_string());
+        self.entries.insert(
+            relative_path.to_string(),
+            Entry {
+                size: metadata.len(),
+                modified: modified_nanos(metadata),
+                info: info.clone(),
+            },
+        );
+        self.dirty = true;
+        info
+    }
+
+    /// 写回磁盘；本次未访问且已不存在的文件会被清理
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let root = PathBuf::from(&self.root);
+        let before = self.entries.len();
+        let seen = &self.seen;
+        self.entries.retain(|relative_path, _| {
+            seen.contains(relative_path) || root.join(relative_path).is_file()
+        });
+        if !self.dirty && self.entries.len() == before {
+            return Ok(());
+        }
+
+        let mut paths: Vec<&String> = self.entries.keys().collect();
+        paths.sort();
+
+        let mut text = format!("{}\t{}\n", FORMAT, self.root);
+        for relative_path in paths {
+            let entry = &self.entries[relative_path];
+            let info = &entry.info;
+            text.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                entry.size,
+                entry
+                    .modified
+                    .map(|m| m.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                info.hash,
+                info.line_count,
+                info.lines.code,
+                info.lines.comment,
+                info.lines.blank,
+                info.tokens,
+                info.language,
+                info.generated.map_or("-", Kind::as_str),
+                info.encoding.unwrap_or("-"),
+                relative_path
+            ));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // 先写临时文件再改名，避免中断时留下半个缓存
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, path)?;
+
+        self.seen.clear();
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+fn parse(text: &str, root: &str) -> Option<HashMap<String, Entry>> {
+    let mut lines = text.lines();
+    let (format, cached_root) = lines.next()?.split_once('\t')?;
+    // 不同目录的哈希前缀碰撞时同样视为无效
+    if format != FORMAT || cached_root != root {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    for line in lines {
+        let fields: Vec<&str> = line.splitn(12, '\t').collect();
+        let [
+            size,
+            modified,
+            hash,
+            line_count,
+            code,
+            comment,
+            blank,
+            tokens,
+            language,
+            generated,
+            encoding,
+            path,
+        ] = fields[..]
+        else {
+            return None;
+        };
+        entries.insert(
+            path.to_string(),
+            Entry {
+                size: size.parse().ok()?,
+                modified: modified.parse().ok(),
+                info: FileInfo {
+                    hash: hash.to_string(),
+                    language: language.to_string(),
+                    line_count: line_count.parse().ok()?,
+                    lines: LineCounts {
+                        code: code.parse().ok()?,
+                        comment: comment.parse().ok()?,
+                        blank: blank.parse().ok()?,
+                    },
+                    tokens: tokens.parse().ok()?,
+                    generated: Kind::parse(generated),
+                    encoding: match encoding {
+                        "-" => None,
+                        label => Some(Encoding::for_label(label.as_bytes())?.name()),
+                    },
+                },
+            },
+        );
+    }
+    Some(entries)
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> Option<u128> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// 内容的 SHA-256（十六进制）
+pub fn hash(content: &str) -> String {
+    hash_bytes(content.as_bytes())
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 缓存目录：`$XDG_CACHE_HOME`、`~/.cache` 或 Windows 的 `%LOCALAPPDATA%` 下的 `code-collector`
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("code-collector"))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 缓存清理
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 删除全部缓存，返回删除的缓存文件数
+pub fn clear() -> io::Result<usize> {
+    remove_where(|_| true)
+}
+
+/// 删除对应目录已不存在或格式过期的缓存，返回删除的缓存文件数
+pub fn prune() -> io::Result<usize> {
+    remove_where(
+        |text| match text.lines().next().and_then(|l| l.split_once('\t')) {
+            Some((format, root)) => format != FORMAT || !Path::new(root).is_dir(),
+            None => true,
+        },
+    )
+}
+
+fn remove_where(stale: impl Fn(&str) -> bool) -> io::Result<usize> {
+    let Some(dir) = cache_dir() else {
+        return Ok(0);
+    };
+    let Ok(items) = fs::read_dir(&dir) else {
+        return Ok(0);
+    };
+
+    let mut removed = 0;
+    for item in items {
+        let path = item?.path();
+        if path.extension().is_none_or(|e| e != "tsv" && e != "tmp") {
+            continue;
+        }
+        if stale(&fs::read_to_string(&path).unwrap_or_default()) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
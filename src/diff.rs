@@ -0,0 +1,345 @@
+use crate::{
+    VERSION, bundle, cache, collect_contents, count_max_backticks, lang, print_colored, secrets,
+};
+use diffy::{DiffOptions, Line, Patch, PatchFormatter};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// diff 子命令：比较两个包或包与目录
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Options {
+    old: PathBuf,
+    new: PathBuf,
+    outfile: Option<PathBuf>,
+    show_patch: bool,
+}
+
+/// 一侧的文件内容：包中解析出的文件，或按默认规则扫描目录得到的文件
+struct Snapshot {
+    files: BTreeMap<String, String>,
+    /// 大纲、精简或脱敏过的文件数；这些文件与原文件比较时必然不同
+    altered: usize,
+    /// 加载时脱敏的文件及处数
+    redactions: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Added => "新增",
+            Self::Removed => "删除",
+            Self::Modified => "修改",
+        }
+    }
+}
+
+struct FileDiff<'a> {
+    path: &'a str,
+    status: Status,
+    old: &'a str,
+    new: &'a str,
+    inserted: usize,
+    deleted: usize,
+    /// 新版本中脱敏的处数
+    redactions: usize,
+}
+
+impl FileDiff<'_> {
+    /// 新增文件以 `/dev/null` 为原文件，删除文件以 `/dev/null` 为新文件，便于 apply 识别
+    fn patch(&self) -> Patch<'_, str> {
+        let original = match self.status {
+            Status::Added => "/dev/null".to_string(),
+            _ => format!("a/{}", self.path),
+        };
+        let modified = match self.status {
+            Status::Removed => "/dev/null".to_string(),
+            _ => format!("b/{}", self.path),
+        };
+        DiffOptions::new()
+            .set_original_filename(original)
+            .set_modified_filename(modified)
+            .create_patch(self.old, self.new)
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(options) = parse_args(args)? else {
+        return Ok(());
+    };
+
+    let old = load(&options.old)?;
+    let new = load(&options.new)?;
+    if old.altered + new.altered > 0 {
+        print_colored(
+            "yellow",
+            "⚠",
+            &format!(
+                "{} 个文件是大纲、精简或脱敏版本，比较结果可能包含非真实的修改",
+                old.altered + new.altered
+            ),
+        );
+    }
+
+    let diffs = compare(&old, &new);
+    let unchanged = old
+        .files
+        .keys()
+        .filter(|p| new.files.contains_key(*p))
+        .count()
+        - diffs
+            .iter()
+            .filter(|d| d.status == Status::Modified)
+            .count();
+
+    for diff in &diffs {
+        let (color, symbol) = match diff.status {
+            Status::Added => ("green", "+"),
+            Status::Removed => ("red", "-"),
+            Status::Modified => ("yellow", "~"),
+        };
+        print_colored(
+            color,
+            symbol,
+            &format!("{}（+{} -{}）", diff.path, diff.inserted, diff.deleted),
+        );
+        if options.show_patch {
+            print!(
+                "{}",
+                PatchFormatter::new().with_color().fmt_patch(&diff.patch())
+            );
+        }
+    }
+
+    let count = |status| diffs.iter().filter(|d| d.status == status).count();
+    println!();
+    print_colored(
+        "blue",
+        "→",
+        &format!(
+            "新增 {} | 删除 {} | 修改 {} | 未变化 {} | 行 +{} -{}",
+            count(Status::Added),
+            count(Status::Removed),
+            count(Status::Modified),
+            unchanged,
+            diffs.iter().map(|d| d.inserted).sum::<usize>(),
+            diffs.iter().map(|d| d.deleted).sum::<usize>()
+        ),
+    );
+
+    if let Some(outfile) = &options.outfile {
+        fs::write(outfile, render(&options, &diffs))?;
+        print_colored("green", "✓", &format!("已生成: {}", outfile.display()));
+    }
+    Ok(())
+}
+
+/// 两侧都按生成时的规则脱敏：已脱敏的包与目录比较时不会因密钥而不同，
+/// 终端差异和 `-OutFile` 中也不会出现密钥原文
+fn load(path: &Path) -> Result<Snapshot, Box<dyn std::error::Error>> {
+    let (files, altered): (Vec<(String, String)>, usize) = if path.is_dir() {
+        (collect_contents(path)?, 0)
+    } else {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("无法读取 {}: {}", path.display(), e))?;
+        let files = bundle::parse(&text, false).files;
+        if files.is_empty() {
+            return Err(format!("未在 {} 中找到文件段落", path.display()).into());
+        }
+        let altered = files.iter().filter(|f| f.altered).count();
+        (
+            files.into_iter().map(|f| (f.path, f.content)).collect(),
+            altered,
+        )
+    };
+
+    let mut snapshot = Snapshot {
+        files: BTreeMap::new(),
+        altered,
+        redactions: BTreeMap::new(),
+    };
+    for (path, content) in files {
+        let (safe, findings) = secrets::redact(&content, &content);
+        if findings.is_empty() {
+            snapshot.files.insert(path, content);
+        } else {
+            snapshot.redactions.insert(path.clone(), findings.len());
+            snapshot.files.insert(path, safe);
+        }
+    }
+    Ok(snapshot)
+}
+
+/// 按路径合并两侧，返回有变化的文件（按路径排序）
+fn compare<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<FileDiff<'a>> {
+    let mut paths: Vec<&String> = old.files.keys().chain(new.files.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let (status, old_content, new_content) =
+                match (old.files.get(path), new.files.get(path)) {
+                    (None, Some(n)) => (Status::Added, "", n.as_str()),
+                    (Some(o), None) => (Status::Removed, o.as_str(), ""),
+                    (Some(o), Some(n)) if o != n => (Status::Modified, o.as_str(), n.as_str()),
+                    _ => return None,
+                };
+
+            let mut diff = FileDiff {
+                path,
+                status,
+                old: old_content,
+                new: new_content,
+                inserted: 0,
+                deleted: 0,
+                redactions: new.redactions.get(path).copied().unwrap_or(0),
+            };
+            let patch = diff.patch();
+            let lines = patch.hunks().iter().flat_map(|h| h.lines());
+            let (inserted, deleted) = lines.fold((0, 0), |(ins, del), line| match line {
+                Line::Insert(_) => (ins + 1, del),
+                Line::Delete(_) => (ins, del + 1),
+                Line::Context(_) => (ins, del),
+            });
+            diff.inserted = inserted;
+            diff.deleted = deleted;
+            Some(diff)
+        })
+        .collect()
+}
+
+/// 只含变更文件的新包：变更表、新版本文件内容和统一差异
+fn render(options: &Options, diffs: &[FileDiff]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# 🔀 变更集：{} → {}\n",
+        options.old.display(),
+        options.new.display()
+    );
+    let _ = writeln!(out, "> 由 Code Collector v{} 自动生成\n", VERSION);
+    let _ = writeln!(out, "| 状态 | 文件 | 增加 | 删除 |");
+    let _ = writeln!(out, "|------|------|------|------|");
+    for diff in diffs {
+        let _ = writeln!(
+            out,
+            "| {} | `{}` | +{} | -{} |",
+            diff.status.label(),
+            diff.path,
+            diff.inserted,
+            diff.deleted
+        );
+    }
+    let _ = writeln!(out, "\n---\n");
+
+    let changed: Vec<&FileDiff> = diffs
+        .iter()
+        .filter(|d| d.status != Status::Removed)
+        .collect();
+    if !changed.is_empty() {
+        let _ = writeln!(out, "## 📄 文件内容\n");
+        for diff in changed {
+            let fence = fence_for(diff.new);
+            let _ = writeln!(out, "### {}\n", diff.path);
+            // 脱敏过的内容不是原文，标记后 apply 不会写回
+            let redacted = match diff.redactions {
+                0 => String::new(),
+                n => format!(" | 🔐 已脱敏 {} 处", n),
+            };
+            let _ = writeln!(
+                out,
+                "> 📏 {} 行 | {} +{} -{}{} | 🔑 {}\n",
+                diff.new.lines().count(),
+                diff.status.label(),
+                diff.inserted,
+                diff.deleted,
+                redacted,
+                &cache::hash(diff.new)[..bundle::HASH_PREFIX]
+            );
+            let _ = writeln!(out, "{}{}", fence, lang::detect(diff.path, diff.new));
+            out.push_str(diff.new);
+            if !diff.new.is_empty() && !diff.new.ends_with('\n') {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "{}\n", fence);
+        }
+    }
+
+    let patches: String = diffs.iter().map(|d| d.patch().to_string()).collect();
+    if !patches.is_empty() {
+        let fence = fence_for(&patches);
+        let _ = writeln!(out, "## 🔀 统一差异\n");
+        let _ = writeln!(out, "{}diff", fence);
+        out.push_str(&patches);
+        let _ = writeln!(out, "{}", fence);
+    }
+    out
+}
+
+/// 比内容中最长的反引号序列多一个，至少 3 个
+fn fence_for(content: &str) -> String {
+    "`".repeat((count_max_backticks(content) + 1).max(3))
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    let mut outfile = None;
+    let mut show_patch = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return Ok(None);
+            }
+            "-OutFile" => {
+                i += 1;
+                outfile = Some(PathBuf::from(args.get(i).ok_or("缺少 -OutFile 的值")?));
+            }
+            "-Patch" => show_patch = true,
+            arg if !arg.starts_with('-') && inputs.len() < 2 => inputs.push(PathBuf::from(arg)),
+            arg => return Err(format!("未知参数: {}", arg).into()),
+        }
+        i += 1;
+    }
+
+    let [old, new] = <[PathBuf; 2]>::try_from(inputs)
+        .map_err(|_| "需要两个参数：旧的包或目录、新的包或目录，用法见 diff -h")?;
+    Ok(Some(Options {
+        old,
+        new,
+        outfile,
+        show_patch,
+    }))
+}
+
+fn print_help() {
+    println!(
+        r#"
+用法:
+  code_collector diff <旧包或目录> <新包或目录> [选项]
+
+比较两个生成的 Markdown 包，或包与当前目录，列出新增、删除和修改的文件及行数变化。
+目录按默认规则扫描（内置忽略目录、二进制、敏感文件等）。
+两侧都按默认规则脱敏疑似密钥，终端差异和 -OutFile 中不会出现密钥原文。
+
+选项:
+  -OutFile <文件>      另存一个只含变更文件的新包，末尾附统一差异
+  -Patch               在终端显示每个文件的彩色差异
+  -h, --help           显示帮助信息
+"#
+    );
+}
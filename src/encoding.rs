@@ -0,0 +1,155 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 文本解码：BOM、UTF-16 与其他编码转为 UTF-8
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 判断无 BOM 的 UTF-16 时抽样的字节数
+const SNIFF_BYTES: usize = 4096;
+
+/// 旧式编码的文本中控制字符（制表、换行、换页、ESC 除外）的最大占比（千分比）
+const MAX_CONTROL_PERMILLE: usize = 10;
+
+/// 解码后的文件内容
+pub struct Decoded {
+    pub text: String,
+    /// 原始编码（encoding_rs 的规范名称，如 `GBK`、`UTF-16LE`）；UTF-8 为 `None`
+    pub encoding: Option<&'static str>,
+}
+
+/// 读取文件并解码；无法确定编码时返回 `InvalidData`
+pub fn read(path: &Path, fallbacks: &[&'static Encoding]) -> io::Result<Decoded> {
+    decode(fs::read(path)?, fallbacks)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "无法识别文件编码"))
+}
+
+/// 依次尝试：UTF-16 的 BOM、无 BOM 的 UTF-16、UTF-8、候选编码
+///
+/// `fallbacks` 为空时只接受 chardetng 有把握的检测结果；不为空时优先用检测结果，
+/// 检测结果不在候选中或解码出错时，取第一个能无错解码的候选编码
+pub fn decode(bytes: Vec<u8>, fallbacks: &[&'static Encoding]) -> Option<Decoded> {
+    // UTF-8 的 BOM 与普通 UTF-8 一样保留在内容中
+    if let Some((encoding, bom)) = Encoding::for_bom(&bytes)
+        && encoding != UTF_8
+    {
+        return transcode(&bytes[bom..], encoding);
+    }
+
+    // ASCII 为主的 UTF-16 同时也是合法的 UTF-8（夹着 NUL），须先判断
+    if let Some(encoding) = sniff_utf16(&bytes) {
+        return transcode(&bytes, encoding);
+    }
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => {
+            return Some(Decoded {
+                text,
+                encoding: None,
+            });
+        }
+        Err(e) => e.into_bytes(),
+    };
+    // 其余候选编码的文本中不会出现 NUL，控制字符也很少，否则视为二进制
+    if bytes.contains(&0) || !mostly_printable(&bytes) {
+        return None;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(&bytes, true);
+    let (guess, confident) = detector.guess_assess(None, false);
+    if fallbacks.is_empty() {
+        return confident.then(|| transcode(&bytes, guess)).flatten();
+    }
+    if fallbacks.contains(&guess)
+        && let Some(decoded) = transcode(&bytes, guess)
+    {
+        return Some(decoded);
+    }
+    fallbacks
+        .iter()
+        .find_map(|&encoding| transcode(&bytes, encoding))
+}
+
+/// 解析 `-Encodings` 的取值（WHATWG 标签，如 `gbk`、`shift_jis`、`windows-1252`）
+pub fn parse_list(value: &str) -> Result<Vec<&'static Encoding>, String> {
+    value
+        .split_whitespace()
+        .map(|label| {
+            Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("未知编码: {}", label))
+        })
+        .collect()
+}
+
+/// 没有 BOM 的 UTF-16：ASCII 为主的文本每两个字节中有一个是 0，且总在同一侧
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES) & !1];
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    // 至少四成的字符高字节为 0，低字节几乎从不为 0
+    if odd * 5 >= pairs * 2 && even * 20 < pairs {
+        Some(UTF_16LE)
+    } else if even * 5 >= pairs * 2 && odd * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// 控制字符不超过 `MAX_CONTROL_PERMILLE`；ESC 用于 ISO-2022-JP 的切换序列，不计入
+fn mostly_printable(bytes: &[u8]) -> bool {
+    let controls = bytes
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    controls * 1000 < bytes.len() * MAX_CONTROL_PERMILLE
+}
+
+fn transcode(bytes: &[u8], encoding: &'static Encoding) -> Option<Decoded> {
+    let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+    Some(Decoded {
+        text: text.into_owned(),
+        encoding: Some(encoding.name()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_without_nul_is_rejected() {
+        // 伪随机字节，不含 NUL
+        let mut state = 0x2545_f491_u32;
+        let bytes: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 255) as u8 + 1
+            })
+            .collect();
+        assert!(decode(bytes.clone(), &[]).is_none());
+        assert!(decode(bytes, &[encoding_rs::WINDOWS_1252]).is_none());
+    }
+
+    #[test]
+    fn legacy_text_is_decoded() {
+        let (bytes, _, _) =
+            encoding_rs::GBK.encode("// 读取配置文件并返回解析后的结果\nfn main() {}\n");
+        let decoded = decode(bytes.into_owned(), &[encoding_rs::GBK]).unwrap();
+        assert_eq!(decoded.encoding, Some("GBK"));
+        assert!(decoded.text.contains("读取配置文件"));
+    }
+}
@@ -0,0 +1,193 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 生成文件与压缩文件识别
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 识别结果，写入缓存和清单
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// 代码生成器的产物（protobuf、bindgen、带 `DO NOT EDIT` 头部等）
+    Generated,
+    /// 压缩后的代码或 source map
+    Minified,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Generated => "generated",
+            Self::Minified => "minified",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "generated" => Some(Self::Generated),
+            "minified" => Some(Self::Minified),
+            _ => None,
+        }
+    }
+}
+
+/// 已知生成器的文件名后缀
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".pb.go",
+    ".pb.gw.go",
+    ".pb.cc",
+    ".pb.h",
+    "_pb2.py",
+    "_pb2.pyi",
+    "_pb2_grpc.py",
+    "_pb.js",
+    "_pb.d.ts",
+    "_grpc_pb.js",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+    ".g.cs",
+    "_generated.go",
+    "_generated.rs",
+    ".generated.ts",
+];
+
+/// 工具生成的锁文件（扩展名为 `.lock` 的已按二进制扩展名跳过）
+const GENERATED_FILES: &[&str] = &["package-lock.json", "npm-shrinkwrap.json", "pnpm-lock.yaml"];
+
+const MINIFIED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.mjs",
+    ".min.css",
+    ".js.map",
+    ".mjs.map",
+    ".cjs.map",
+    ".css.map",
+    ".d.ts.map",
+];
+
+/// 注释行的开头：`//`、`#`、`/*`、块注释中的 `*`、`--`、`;`、`<!--`、`(*`
+const COMMENT: &str = r"^\s*(?://+!?|#+|/\*+!?|\*|--|;+|<!--|\(\*)\s*";
+
+/// Markdown 只有 HTML 注释；`#` 标题和 `*`、`-` 列表是正文
+const MARKUP_COMMENT: &str = r"^\s*<!--\s*";
+
+/// 生成标记的正文部分，与注释前缀拼接
+const MARKERS: &[&str] = &[
+    // Go 的约定：`// Code generated by X. DO NOT EDIT.`
+    r"Code generated .* DO NOT EDIT\.?\s*(?:\*/)?$",
+    r".*@generated\b",
+    r"(?i)<auto-generated",
+    r"(?i)(?:this (?:file|code) (?:is|was|has been) )?(?:auto-?generated|automatically generated|generated by)\b",
+    r"(?i)do not edit\b",
+];
+
+fn header_markers(comment: &str) -> Vec<Regex> {
+    MARKERS
+        .iter()
+        .map(|marker| {
+            let pattern = format!("{}{}", comment, marker);
+            Regex::new(&pattern).expect("内置生成标记必须是合法正则")
+        })
+        .collect()
+}
+
+/// 出现在文件开头几行注释中的生成标记；只匹配注释行，说明这些标记的普通文字不算
+static HEADER_MARKERS: LazyLock<Vec<Regex>> = LazyLock::new(|| header_markers(COMMENT));
+
+/// Markdown 中的生成标记，只认 HTML 注释
+static MARKUP_HEADER_MARKERS: LazyLock<Vec<Regex>> =
+    LazyLock::new(|| header_markers(MARKUP_COMMENT));
+
+/// 检查生成标记的行数
+const HEADER_LINES: usize = 10;
+
+/// 超过这个字符数的行算作“长行”
+const LONG_LINE: usize = 500;
+
+/// 行长度判断只对不小于这个字节数的文件生效，避免误判很短的单行文件
+const MIN_SIZE_FOR_LENGTH: usize = 1024;
+
+/// 按文件名、头部标记和行长度判断；`language` 为识别出的语言
+pub fn detect(relative_path: &str, content: &str, language: &str) -> Option<Kind> {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    let lower = file_name.to_lowercase();
+
+    if MINIFIED_SUFFIXES.iter().any(|s| lower.ends_with(s)) {
+        return Some(Kind::Minified);
+    }
+    if GENERATED_FILES.contains(&lower.as_str())
+        || GENERATED_SUFFIXES.iter().any(|s| lower.ends_with(s))
+    {
+        return Some(Kind::Generated);
+    }
+
+    // 纯文本没有注释，其中的标记都是正文
+    let markers: &[Regex] = match language {
+        "markdown" => &MARKUP_HEADER_MARKERS,
+        "plaintext" => &[],
+        _ => &HEADER_MARKERS,
+    };
+    let has_marker = content
+        .lines()
+        .take(HEADER_LINES)
+        .any(|line| markers.iter().any(|m| m.is_match(line)));
+    if has_marker {
+        return Some(Kind::Generated);
+    }
+
+    // 散文的长段落不算压缩代码
+    if !matches!(language, "markdown" | "plaintext") && looks_minified(content) {
+        return Some(Kind::Minified);
+    }
+    None
+}
+
+/// 平均行长超过 200 个字符，或至少五分之一的行超过 `LONG_LINE`
+fn looks_minified(content: &str) -> bool {
+    if content.len() < MIN_SIZE_FOR_LENGTH {
+        return false;
+    }
+    let mut lines = 0;
+    let mut chars = 0;
+    let mut long = 0;
+    for line in content.lines() {
+        let len = line.chars().count();
+        lines += 1;
+        chars += len;
+        if len > LONG_LINE {
+            long += 1;
+        }
+    }
+    lines > 0 && (chars / lines > 200 || long * 5 >= lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_comment_marks_file_as_generated() {
+        let content = "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage pb\n";
+        assert!(detect("api/user.go", content, "go") == Some(Kind::Generated));
+        let content = "# @generated by a tool\nx = 1\n";
+        assert!(detect("gen.py", content, "python") == Some(Kind::Generated));
+    }
+
+    #[test]
+    fn prose_mentioning_markers_is_kept() {
+        let content = "Files whose header says \"Code generated ... DO NOT EDIT.\" are skipped.\n\
+                       let s = \"do not edit\";\n";
+        assert!(detect("notes.txt", content, "plaintext").is_none());
+        assert!(detect("src/generated.rs", include_str!("generated.rs"), "rust").is_none());
+    }
+
+    #[test]
+    fn markdown_headings_and_bullets_are_not_comments() {
+        let readme = "# Generated by our release tooling\n\n* Do not edit the changelog by hand\n";
+        assert!(detect("README.md", readme, "markdown").is_none());
+        assert!(detect("notes.txt", readme, "plaintext").is_none());
+        let content = "<!-- DO NOT EDIT: generated from docs/api.yaml -->\n# API\n";
+        assert!(detect("docs/api.md", content, "markdown") == Some(Kind::Generated));
+    }
+}
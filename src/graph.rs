@@ -0,0 +1,193 @@
+use crate::imports;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 模块依赖图
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    /// 写入 Markdown 的 Mermaid 代码块
+    Mermaid,
+    /// 单独输出 `.dot` 文件
+    Dot,
+}
+
+impl GraphFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "mermaid" => Some(Self::Mermaid),
+            "dot" | "graphviz" => Some(Self::Dot),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mermaid => "mermaid",
+            Self::Dot => "dot",
+        }
+    }
+}
+
+/// 文件（或折叠后的目录）之间的依赖关系
+pub struct Graph {
+    nodes: BTreeSet<String>,
+    edges: BTreeSet<(String, String)>,
+    /// 是否已折叠为目录节点；折叠后不再按目录分组
+    collapsed: bool,
+}
+
+impl Graph {
+    /// 解析收集到的文件之间的导入关系；`files` 为（相对路径, 语言, 解码后的内容）
+    ///
+    /// `collapse` 为 `Some(n)` 时把节点折叠为前 n 层目录，便于阅读大型项目。
+    pub fn build(base: &Path, files: &[(&str, &str, &str)], collapse: Option<usize>) -> Self {
+        let collected: HashSet<&str> = files.iter().map(|(path, _, _)| *path).collect();
+        let node_of = |path: &str| match collapse {
+            Some(level) => package_of(path, level),
+            None => path.to_string(),
+        };
+
+        let mut nodes = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+
+        for (path, lang, content) in files {
+            let from = node_of(path);
+            nodes.insert(from.clone());
+
+            for import in imports::local_imports(base, path, content, lang) {
+                if !collected.contains(import.as_str()) {
+                    continue;
+                }
+                let to = node_of(&import);
+                if to != from {
+                    edges.insert((from.clone(), to));
+                }
+            }
+        }
+
+        Self {
+            nodes,
+            edges,
+            collapsed: collapse.is_some(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// 只保留有边相连的节点，按所在目录分组
+    fn groups(&self) -> BTreeMap<String, Vec<&String>> {
+        let connected: HashSet<&String> = self.edges.iter().flat_map(|(a, b)| [a, b]).collect();
+        let mut groups: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+        for node in self.nodes.iter().filter(|n| connected.contains(n)) {
+            let dir = if self.collapsed {
+                String::new()
+            } else {
+                node.rsplit_once('/')
+                    .map(|(dir, _)| dir.to_string())
+                    .unwrap_or_default()
+            };
+            groups.entry(dir).or_default().push(node);
+        }
+        groups
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut ids: BTreeMap<&String, String> = BTreeMap::new();
+        let mut out = String::from("flowchart LR\n");
+
+        for (i, (dir, nodes)) in self.groups().into_iter().enumerate() {
+            let indent = if dir.is_empty() { "    " } else { "        " };
+            if !dir.is_empty() {
+                let _ = writeln!(out, "    subgraph g{}[\"{}/\"]", i, escape_mermaid(&dir));
+            }
+            for node in nodes {
+                let id = format!("n{}", ids.len());
+                let _ = writeln!(
+                    out,
+                    "{}{}[\"{}\"]",
+                    indent,
+                    id,
+                    escape_mermaid(label(node, self.collapsed))
+                );
+                ids.insert(node, id);
+            }
+            if !dir.is_empty() {
+                out.push_str("    end\n");
+            }
+        }
+
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "    {} --> {}", ids[from], ids[to]);
+        }
+        out
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (i, (dir, nodes)) in self.groups().into_iter().enumerate() {
+            let indent = if dir.is_empty() { "    " } else { "        " };
+            if !dir.is_empty() {
+                let _ = writeln!(out, "    subgraph cluster_{} {{", i);
+                let _ = writeln!(out, "        label=\"{}/\";", escape(&dir));
+            }
+            for node in nodes {
+                let _ = writeln!(
+                    out,
+                    "{}\"{}\" [label=\"{}\"];",
+                    indent,
+                    escape(node),
+                    escape(label(node, self.collapsed))
+                );
+            }
+            if !dir.is_empty() {
+                out.push_str("    }\n");
+            }
+        }
+
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "    \"{}\" -> \"{}\";", escape(from), escape(to));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// 文件所属的前 `level` 层目录；根目录下的文件归为 `.`
+fn package_of(path: &str, level: usize) -> String {
+    let dirs: Vec<&str> = path.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    if dirs.is_empty() || level == 0 {
+        ".".to_string()
+    } else {
+        dirs[..level.min(dirs.len())].join("/")
+    }
+}
+
+/// 分组内显示文件名，折叠后显示目录路径
+fn label(node: &str, collapsed: bool) -> &str {
+    if collapsed {
+        node
+    } else {
+        node.rsplit('/').next().unwrap_or(node)
+    }
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,398 @@
+use crate::{encoding, loc};
+use encoding_rs::Encoding;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 导入解析：从源码中找出引用的本地文件
+// ═══════════════════════════════════════════════════════════════════════════
+
+const JS_EXTS: &[&str] = &["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs", "json"];
+
+/// 解析文件中的导入/模块声明，返回存在于 `base` 下的本地文件（相对路径，`/` 分隔）
+pub fn local_imports(base: &Path, relative_path: &str, content: &str, lang: &str) -> Vec<String> {
+    let code = blank_comments(content, lang);
+    let file = base.join(relative_path);
+
+    let mut found: Vec<PathBuf> = match lang {
+        "rust" => rust_imports(&file, &code),
+        "javascript" | "typescript" | "tsx" | "jsx" | "vue" | "svelte" => js_imports(&file, &code),
+        "python" => python_imports(base, &file, &code),
+        _ => Vec::new(),
+    };
+
+    let mut seen = HashSet::new();
+    found.retain(|p| p.is_file());
+    found
+        .into_iter()
+        .filter_map(|p| relative_to(base, &p))
+        .filter(|p| p != relative_path && seen.insert(p.clone()))
+        .collect()
+}
+
+/// 从入口文件出发沿导入关系遍历，返回可达文件集合（含入口本身）
+///
+/// `max_depth` 限制遍历层数，入口为第 0 层；`language_of` 按（相对路径, 内容）识别语言；
+/// 非 UTF-8 文件按 `encodings` 解码。
+pub fn reachable(
+    base: &Path,
+    entries: &[String],
+    max_depth: Option<usize>,
+    encodings: &[&'static Encoding],
+    language_of: impl Fn(&str, &str) -> &'static str,
+) -> HashSet<String> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<(String, usize)> = VecDeque::new();
+
+    for entry in entries {
+        if visited.insert(entry.clone()) {
+            queue.push_back((entry.clone(), 0));
+        }
+    }
+
+    while let Some((path, depth)) = queue.pop_front() {
+        if max_depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+        // 无法识别编码的文件（二进制）没有导入
+        let Ok(decoded) = encoding::read(&base.join(&path), encodings) else {
+            continue;
+        };
+        let content = decoded.text;
+        for import in local_imports(base, &path, &content, language_of(&path, &content)) {
+            if visited.insert(import.clone()) {
+                queue.push_back((import, depth + 1));
+            }
+        }
+    }
+
+    visited
+}
+
+/// 把注释替换为空格（保留换行），避免解析被注释掉的导入
+fn blank_comments(content: &str, lang: &str) -> String {
+    let Some(syntax) = loc::syntax_for(lang) else {
+        return content.to_string();
+    };
+
+    let mut code = content.as_bytes().to_vec();
+    for comment in loc::scan_comments(content, syntax) {
+        for b in &mut code[comment.start..comment.end] {
+            if *b != b'\n' {
+                *b = b' ';
+            }
+        }
+    }
+    String::from_utf8_lossy(&code).into_owned()
+}
+
+fn relative_to(base: &Path, path: &Path) -> Option<String> {
+    let path = normalize(path);
+    let base = normalize(base);
+    path.strip_prefix(&base)
+        .ok()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+}
+
+/// 去掉路径中的 `.` 和 `..`，不访问文件系统
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// 取出 `keyword` 之后的标识符路径，如 `mod foo;` 中的 `foo`
+fn words_after<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix(keyword)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim_start())
+}
+
+fn quoted_strings(text: &str) -> Vec<&str> {
+    let mut strings = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['"', '\'', '`']) {
+        let quote = rest[start..].chars().next().unwrap_or('"');
+        let after = &rest[start + 1..];
+        match after.find(quote) {
+            Some(end) => {
+                strings.push(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+    strings
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Rust：`mod foo;`、`#[path = "..."]`、`use crate::/super::/self::`
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn rust_imports(file: &Path, code: &str) -> Vec<PathBuf> {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    let module_dir = rust_module_dir(file);
+    let crate_root = rust_crate_root(file);
+    let mut found = Vec::new();
+    let mut path_attr: Option<String> = None;
+
+    for line in code.lines() {
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix("#[path") {
+            path_attr = quoted_strings(rest).first().map(|s| s.to_string());
+            continue;
+        }
+
+        let line = line.strip_prefix("pub ").unwrap_or(line);
+        let line = if line.starts_with("pub(") {
+            line.split_once(')')
+                .map_or(line, |(_, rest)| rest.trim_start())
+        } else {
+            line
+        };
+
+        if let Some(rest) = words_after(line, "mod") {
+            let name = rest.trim_end_matches(';').trim();
+            if rest.ends_with(';') && is_identifier(name) {
+                match path_attr.take() {
+                    Some(path) => found.push(parent.join(path)),
+                    None => {
+                        found.push(module_dir.join(format!("{}.rs", name)));
+                        found.push(module_dir.join(name).join("mod.rs"));
+                    }
+                }
+            }
+        } else if let Some(rest) = words_after(line, "use") {
+            let mut segments = rest.split("::").map(|s| s.trim());
+            let start = match segments.next() {
+                Some("crate") => crate_root.clone(),
+                Some("self") => module_dir.clone(),
+                Some("super") => {
+                    let mut dir = module_dir
+                        .parent()
+                        .map(Path::to_path_buf)
+                        .unwrap_or_default();
+                    for segment in segments.clone() {
+                        if segment != "super" {
+                            break;
+                        }
+                        dir = dir.parent().map(Path::to_path_buf).unwrap_or_default();
+                    }
+                    dir
+                }
+                _ => continue,
+            };
+            // 沿路径逐段尝试 `a.rs`、`a/mod.rs`，遇到非模块名（如 `{`、类型）即停止
+            let mut dir = start;
+            for segment in segments
+                .map(|s| s.trim_end_matches(';'))
+                .filter(|s| *s != "super")
+            {
+                if !is_identifier(segment) {
+                    break;
+                }
+                found.push(dir.join(format!("{}.rs", segment)));
+                found.push(dir.join(segment).join("mod.rs"));
+                dir = dir.join(segment);
+            }
+        }
+
+        if !line.is_empty() && !line.starts_with("#[") {
+            path_attr = None;
+        }
+    }
+
+    found
+}
+
+/// 子模块所在目录：`main.rs`/`lib.rs`/`mod.rs` 为其所在目录，`foo.rs` 为同级的 `foo/`
+fn rust_module_dir(file: &Path) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    let stem = file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    if matches!(stem, "main" | "lib" | "mod") {
+        parent.to_path_buf()
+    } else {
+        parent.join(stem)
+    }
+}
+
+/// 向上查找包含 `lib.rs` 或 `main.rs` 的目录作为 crate 根
+fn rust_crate_root(file: &Path) -> PathBuf {
+    let mut dir = file.parent();
+    while let Some(d) = dir {
+        if d.join("lib.rs").is_file() || d.join("main.rs").is_file() {
+            return d.to_path_buf();
+        }
+        dir = d.parent();
+    }
+    file.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+fn is_identifier(s: &str) -> bool {
+    let s = s.strip_prefix("r#").unwrap_or(s);
+    !s.is_empty()
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// JavaScript / TypeScript：`import`、`export ... from`、`require()`、`import()`
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn js_imports(file: &Path, code: &str) -> Vec<PathBuf> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut found = Vec::new();
+
+    // 语句可能跨行（多行 import 列表），按关键字定位其后紧跟的字符串
+    for keyword in ["from", "import", "require"] {
+        for (i, _) in code.match_indices(keyword) {
+            let boundary = code[..i]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '_' | '$' | '.')));
+            if boundary && let Some(spec) = next_specifier(&code[i + keyword.len()..]) {
+                found.extend(resolve_js(dir, spec));
+            }
+        }
+    }
+
+    found
+}
+
+/// 关键字后紧跟的相对路径字符串（中间只允许空白和括号）
+fn next_specifier(text: &str) -> Option<&str> {
+    let rest = text.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+    let quote = rest
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let body = &rest[1..];
+    let end = body.find(quote)?;
+    let spec = &body[..end];
+    spec.starts_with('.').then_some(spec)
+}
+
+fn resolve_js(dir: &Path, spec: &str) -> Option<PathBuf> {
+    let target = dir.join(spec);
+    if target.is_file() {
+        return Some(target);
+    }
+
+    // TypeScript ESM 习惯以 `.js` 引用 `.ts` 文件
+    let stem_target = match spec.rsplit_once('.') {
+        Some((stem, "js" | "jsx" | "mjs" | "cjs")) if !stem.is_empty() => dir.join(stem),
+        _ => target.clone(),
+    };
+
+    let file_name = stem_target.file_name()?.to_string_lossy().to_string();
+    JS_EXTS
+        .iter()
+        .map(|ext| stem_target.with_file_name(format!("{}.{}", file_name, ext)))
+        .chain(
+            JS_EXTS
+                .iter()
+                .map(|ext| target.join(format!("index.{}", ext))),
+        )
+        .find(|p| p.is_file())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Python：`import a.b`、`from a.b import c`、`from . import x`
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn python_imports(base: &Path, file: &Path, code: &str) -> Vec<PathBuf> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut found = Vec::new();
+
+    // 绝对导入依次尝试文件所在目录的各级祖先，直到项目根目录
+    let mut roots: Vec<PathBuf> = Vec::new();
+    let mut current = Some(dir);
+    while let Some(d) = current {
+        roots.push(d.to_path_buf());
+        if d == base {
+            break;
+        }
+        current = d.parent();
+    }
+
+    for line in code.lines() {
+        let line = line.trim();
+
+        if let Some(rest) = words_after(line, "import") {
+            for module in rest.split(',') {
+                let module = module.split_whitespace().next().unwrap_or_default();
+                found.extend(python_module(&roots, module, None));
+            }
+        } else if let Some(rest) = words_after(line, "from") {
+            let Some((module, names)) = rest.split_once(" import ") else {
+                continue;
+            };
+            let module = module.trim();
+            let names: Vec<&str> = names
+                .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+                .split(',')
+                .filter_map(|n| n.split_whitespace().next())
+                .collect();
+
+            let dots = module.chars().take_while(|&c| c == '.').count();
+            if dots > 0 {
+                let mut package = dir.to_path_buf();
+                for _ in 1..dots {
+                    package.pop();
+                }
+                found.extend(python_module(&[package], &module[dots..], Some(&names)));
+            } else {
+                found.extend(python_module(&roots, module, Some(&names)));
+            }
+        }
+    }
+
+    found
+}
+
+/// 模块路径对应的文件；`names` 为 `from x import a, b` 中可能是子模块的名字
+fn python_module(roots: &[PathBuf], module: &str, names: Option<&[&str]>) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for root in roots {
+        let package = module
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .fold(root.clone(), |p, s| p.join(s));
+
+        let module_file = package.with_extension("py");
+        if !module.is_empty() && module_file.is_file() {
+            found.push(module_file);
+        }
+        let init = package.join("__init__.py");
+        if init.is_file() {
+            found.push(init);
+        }
+        for name in names.unwrap_or_default() {
+            let submodule = package.join(format!("{}.py", name));
+            if submodule.is_file() {
+                found.push(submodule);
+            }
+        }
+
+        if !found.is_empty() {
+            break;
+        }
+    }
+    found
+}
@@ -0,0 +1,210 @@
+use crate::get_language;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 语言识别：文件名 → shebang → 编辑器模式行 → 扩展名
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 按固定文件名识别的语言（比较时忽略大小写）
+const FILENAMES: &[(&str, &str)] = &[
+    ("dockerfile", "dockerfile"),
+    ("containerfile", "dockerfile"),
+    ("makefile", "makefile"),
+    ("gnumakefile", "makefile"),
+    ("cmakelists.txt", "cmake"),
+    ("jenkinsfile", "groovy"),
+    ("rakefile", "ruby"),
+    ("gemfile", "ruby"),
+    ("guardfile", "ruby"),
+    ("podfile", "ruby"),
+    ("vagrantfile", "ruby"),
+    ("brewfile", "ruby"),
+    ("pkgbuild", "bash"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".bash_aliases", "bash"),
+    (".bash_logout", "bash"),
+    (".profile", "bash"),
+    (".zshrc", "bash"),
+    (".zshenv", "bash"),
+    (".zprofile", "bash"),
+    (".editorconfig", "ini"),
+    (".gitconfig", "ini"),
+    ("build.bazel", "python"),
+    ("workspace.bazel", "python"),
+];
+
+/// 模式行只在文件开头和结尾的这几行中查找（与 Vim 的 `modelines` 默认值一致）
+const MODELINE_LINES: usize = 5;
+
+/// 识别文件语言；`path` 为相对路径或文件名，`content` 为文件内容
+pub fn detect(path: &str, content: &str) -> &'static str {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if let Some(language) = from_filename(file_name) {
+        return language;
+    }
+    if let Some(language) = from_shebang(content) {
+        return language;
+    }
+    if let Some(language) = from_modeline(content) {
+        return language;
+    }
+    let ext = file_name
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .unwrap_or_default();
+    get_language(&ext)
+}
+
+fn from_filename(file_name: &str) -> Option<&'static str> {
+    let lower = file_name.to_lowercase();
+    if let Some(&(_, language)) = FILENAMES.iter().find(|(name, _)| *name == lower) {
+        return Some(language);
+    }
+    // `Dockerfile.dev`、`Makefile.am` 之类的变体
+    let (stem, _) = lower.split_once('.')?;
+    match stem {
+        "dockerfile" | "containerfile" => Some("dockerfile"),
+        "makefile" => Some("makefile"),
+        _ => None,
+    }
+}
+
+/// `#!/usr/bin/env python3`、`#!/bin/bash -e`、`#!/usr/bin/env -S node --flag`
+fn from_shebang(content: &str) -> Option<&'static str> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = basename(words.next()?);
+    if program == "env" {
+        // 跳过 env 自己的选项和 `NAME=value` 赋值
+        program = basename(words.find(|w| !w.starts_with('-') && !w.contains('='))?);
+    }
+    from_interpreter(program)
+}
+
+fn from_interpreter(program: &str) -> Option<&'static str> {
+    // python3.11、ruby2.7、perl5 等带版本号的解释器
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    Some(match name {
+        "python" | "pypy" => "python",
+        "node" | "nodejs" | "bun" => "javascript",
+        "deno" | "ts-node" | "tsx" => "typescript",
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => "bash",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "Rscript" => "r",
+        "pwsh" | "powershell" => "powershell",
+        "groovy" => "groovy",
+        "scala" => "scala",
+        "kotlin" => "kotlin",
+        "swift" => "swift",
+        "dart" => "dart",
+        "elixir" => "elixir",
+        "escript" => "erlang",
+        "runghc" | "runhaskell" | "stack" => "haskell",
+        "ocaml" => "ocaml",
+        "make" => "makefile",
+        _ => return None,
+    })
+}
+
+/// Vim：`vim: set ft=python:`、`vi: filetype=sh`；Emacs：`-*- mode: ruby -*-`、`-*- python -*-`
+fn from_modeline(content: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = content.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+    lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail))
+        .find_map(|line| emacs_mode(line).or_else(|| vim_filetype(line)))
+        .and_then(from_alias)
+}
+
+fn emacs_mode(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (inner, _) = rest.split_once("-*-")?;
+    let inner = inner.trim();
+    if !inner.contains(':') {
+        return Some(inner).filter(|mode| !mode.is_empty());
+    }
+    inner.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case("mode")
+            .then(|| value.trim())
+    })
+}
+
+fn vim_filetype(line: &str) -> Option<&str> {
+    let start = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|marker| {
+            let at = line.find(marker)?;
+            // 标记前须是行首或空白，避免匹配 `envi:` 之类的文字
+            let preceded = line[..at]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace);
+            preceded.then_some(at + marker.len())
+        })
+        .min()?;
+    line[start..]
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|option| {
+            option
+                .strip_prefix("ft=")
+                .or_else(|| option.strip_prefix("filetype="))
+                .or_else(|| option.strip_prefix("syntax="))
+                .or_else(|| option.strip_prefix("syn="))
+        })
+        .filter(|name| !name.is_empty())
+}
+
+/// 把模式行中的名称映射为语言：先按语言名，再按扩展名
+fn from_alias(alias: &str) -> Option<&'static str> {
+    let alias = alias.to_lowercase();
+    let language = match alias.as_str() {
+        "python" => "python",
+        "rust" => "rust",
+        "ruby" => "ruby",
+        "perl" | "cperl" => "perl",
+        "javascript" | "js2" => "javascript",
+        "typescript" => "typescript",
+        "sh" | "shell" | "shell-script" | "zsh" | "bash" => "bash",
+        "make" | "makefile" => "makefile",
+        "dockerfile" => "dockerfile",
+        "cmake" => "cmake",
+        "c++" => "cpp",
+        "c#" => "csharp",
+        "yaml" => "yaml",
+        "json" => "json",
+        "go" => "go",
+        "java" => "java",
+        "kotlin" => "kotlin",
+        "groovy" => "groovy",
+        "haskell" => "haskell",
+        "elixir" => "elixir",
+        "erlang" => "erlang",
+        "lua" => "lua",
+        "php" => "php",
+        "conf" | "dosini" | "conf-unix" => "ini",
+        other => get_language(other),
+    };
+    (language != "plaintext").then_some(language)
+}
+
+/// `.gitattributes` 中 `linguist-language` 的取值（GitHub 的语言名，如 `Shell`、`C++`）；
+/// 不认识的名称按小写原样使用
+pub fn from_name(name: &str) -> String {
+    from_alias(name)
+        .map(str::to_string)
+        .unwrap_or_else(|| name.to_lowercase().replace(' ', "-"))
+}
+
+fn basename(program: &str) -> &str {
+    program.rsplit(['/', '\\']).next().unwrap_or(program)
+}
@@ -0,0 +1,512 @@
+use std::ops::{AddAssign, SubAssign};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 注释语法定义
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 单门语言的注释与字符串语法，用于区分代码、注释和空行
+pub struct Syntax {
+    /// 行注释前缀
+    line: &'static [&'static str],
+    /// 块注释（开始, 结束）
+    block: &'static [(&'static str, &'static str)],
+    /// 块注释是否允许嵌套
+    nested: bool,
+    /// 字符串定界符（定界符, 是否处理反斜杠转义），长的放前面
+    quotes: &'static [(&'static str, bool)],
+    /// 文档注释前缀
+    doc: &'static [&'static str],
+    /// `'` 仅在构成字符字面量时才视为字符串（Rust 生命周期）
+    char_literals: bool,
+    /// 支持 Rust 风格原始字符串 `r#"..."#`
+    raw_strings: bool,
+    /// 行注释只在行首或空白之后开始（shell 的 `$#`、`${#x}`、`a#b` 不是注释）
+    word_comments: bool,
+    /// 引号只在值的开头才开始字符串（YAML 普通标量 `Don't panic` 中的 `'` 不是引号）
+    scalar_quotes: bool,
+}
+
+const NO_QUOTES: &[(&str, bool)] = &[];
+const C_QUOTES: &[(&str, bool)] = &[("\"", true), ("'", true)];
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const JAVADOC: &[&str] = &["/**"];
+
+const fn c_like(nested: bool, quotes: &'static [(&'static str, bool)]) -> Syntax {
+    Syntax {
+        line: &["//"],
+        block: C_BLOCK,
+        nested,
+        quotes,
+        doc: JAVADOC,
+        char_literals: false,
+        raw_strings: false,
+        word_comments: false,
+        scalar_quotes: false,
+    }
+}
+
+const fn hash_like(quotes: &'static [(&'static str, bool)]) -> Syntax {
+    Syntax {
+        line: &["#"],
+        block: &[],
+        nested: false,
+        quotes,
+        doc: &[],
+        char_literals: false,
+        raw_strings: false,
+        word_comments: false,
+        scalar_quotes: false,
+    }
+}
+
+const RUST: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    nested: true,
+    quotes: &[("\"", true)],
+    doc: &["///", "//!", "/**", "/*!"],
+    char_literals: true,
+    raw_strings: true,
+    word_comments: false,
+    scalar_quotes: false,
+};
+const C_FAMILY: Syntax = c_like(false, C_QUOTES);
+const C_NESTED: Syntax = c_like(true, C_QUOTES);
+const JS_FAMILY: Syntax = c_like(false, &[("\"", true), ("'", true), ("`", true)]);
+const GO: Syntax = c_like(false, &[("\"", true), ("'", true), ("`", false)]);
+const CSHARP: Syntax = Syntax {
+    doc: &["///", "/**"],
+    ..C_FAMILY
+};
+const CSS: Syntax = Syntax {
+    line: &[],
+    doc: &[],
+    ..c_like(false, C_QUOTES)
+};
+const PHP: Syntax = Syntax {
+    line: &["//", "#"],
+    ..c_like(false, C_QUOTES)
+};
+const HCL: Syntax = Syntax {
+    line: &["#", "//"],
+    doc: &[],
+    ..c_like(false, &[("\"", true)])
+};
+const PYTHON: Syntax = hash_like(&[("\"\"\"", true), ("'''", true), ("\"", true), ("'", true)]);
+const ELIXIR: Syntax = hash_like(&[("\"\"\"", true), ("\"", true), ("'", true)]);
+const HASH: Syntax = hash_like(C_QUOTES);
+const SHELL: Syntax = Syntax {
+    word_comments: true,
+    ..HASH
+};
+const YAML: Syntax = Syntax {
+    word_comments: true,
+    scalar_quotes: true,
+    ..HASH
+};
+const HASH_PLAIN: Syntax = hash_like(NO_QUOTES);
+const INI: Syntax = Syntax {
+    line: &["#", ";"],
+    ..hash_like(NO_QUOTES)
+};
+const POWERSHELL: Syntax = Syntax {
+    block: &[("<#", "#>")],
+    ..hash_like(C_QUOTES)
+};
+const NIM: Syntax = Syntax {
+    block: &[("#[", "]#")],
+    nested: true,
+    doc: &["##"],
+    ..hash_like(&[("\"\"\"", true), ("\"", true), ("'", true)])
+};
+const MARKUP: Syntax = Syntax {
+    line: &[],
+    block: &[("<!--", "-->")],
+    ..hash_like(NO_QUOTES)
+};
+const SQL: Syntax = Syntax {
+    line: &["--"],
+    block: C_BLOCK,
+    ..hash_like(C_QUOTES)
+};
+const LUA: Syntax = Syntax {
+    line: &["--"],
+    block: &[("--[[", "]]")],
+    doc: &["---"],
+    ..hash_like(C_QUOTES)
+};
+const HASKELL: Syntax = Syntax {
+    line: &["--"],
+    block: &[("{-", "-}")],
+    nested: true,
+    doc: &["-- |", "{-|"],
+    ..hash_like(&[("\"", true)])
+};
+const ML: Syntax = Syntax {
+    line: &[],
+    block: &[("(*", "*)")],
+    nested: true,
+    doc: &["(**"],
+    ..hash_like(&[("\"", true)])
+};
+const FSHARP: Syntax = Syntax {
+    line: &["//"],
+    doc: &["///", "(**"],
+    ..ML
+};
+const DOUBLE_DASH: Syntax = Syntax {
+    line: &["--"],
+    ..hash_like(&[("\"", true)])
+};
+const PERCENT: Syntax = Syntax {
+    line: &["%"],
+    doc: &["%%%"],
+    ..hash_like(&[("\"", true)])
+};
+const SEMICOLON: Syntax = Syntax {
+    line: &[";"],
+    doc: &[";;;"],
+    ..hash_like(&[("\"", true)])
+};
+
+/// 根据语言（`get_language` 的返回值）获取注释语法；无注释语法的语言返回 `None`
+pub fn syntax_for(lang: &str) -> Option<&'static Syntax> {
+    let syntax = match lang {
+        "rust" => &RUST,
+        "c" | "cpp" | "java" | "groovy" | "protobuf" | "verilog" | "zig" => &C_FAMILY,
+        "swift" | "kotlin" | "scala" | "dart" => &C_NESTED,
+        "javascript" | "typescript" | "tsx" | "jsx" => &JS_FAMILY,
+        "go" => &GO,
+        "csharp" => &CSHARP,
+        "css" => &CSS,
+        "php" => &PHP,
+        "hcl" => &HCL,
+        "python" => &PYTHON,
+        "elixir" => &ELIXIR,
+        "ruby" | "perl" | "r" | "toml" | "graphql" => &HASH,
+        "bash" => &SHELL,
+        "yaml" => &YAML,
+        "makefile" | "cmake" | "dockerfile" => &HASH_PLAIN,
+        "ini" => &INI,
+        "powershell" => &POWERSHELL,
+        "nim" => &NIM,
+        "html" | "xml" | "markdown" | "vue" | "svelte" => &MARKUP,
+        "sql" => &SQL,
+        "lua" => &LUA,
+        "haskell" | "elm" => &HASKELL,
+        "ocaml" => &ML,
+        "fsharp" => &FSHARP,
+        "vhdl" => &DOUBLE_DASH,
+        "erlang" => &PERCENT,
+        "clojure" => &SEMICOLON,
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 注释扫描
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 源码中的一段注释（字节区间，行注释不含换行符），`doc` 表示文档注释
+#[derive(Clone, Copy)]
+pub struct Comment {
+    pub start: usize,
+    pub end: usize,
+    pub doc: bool,
+}
+
+/// 扫描内容中的所有注释，跳过字符串中的注释标记
+pub fn scan_comments(content: &str, syntax: &Syntax) -> Vec<Comment> {
+    let bytes = content.as_bytes();
+    let mut comments = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+
+        // 块注释优先，避免 Lua 的 `--[[` 被当作行注释
+        if let Some((open, close)) = syntax
+            .block
+            .iter()
+            .find(|(open, _)| rest.starts_with(open.as_bytes()))
+        {
+            let end = block_end(bytes, i + open.len(), open, close, syntax.nested);
+            comments.push(Comment {
+                start: i,
+                end,
+                doc: is_doc(&content[i..end], syntax),
+            });
+            i = end;
+            continue;
+        }
+
+        if syntax
+            .line
+            .iter()
+            .any(|prefix| rest.starts_with(prefix.as_bytes()))
+            && (!syntax.word_comments || i == 0 || bytes[i - 1].is_ascii_whitespace())
+        {
+            let end = rest
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |p| i + p);
+            let end = if end > i && bytes[end - 1] == b'\r' {
+                end - 1
+            } else {
+                end
+            };
+            comments.push(Comment {
+                start: i,
+                end,
+                doc: is_doc(&content[i..end], syntax),
+            });
+            i = end;
+            continue;
+        }
+
+        if syntax.raw_strings
+            && let Some(end) = raw_string_end(bytes, i)
+        {
+            i = end;
+            continue;
+        }
+
+        if syntax.char_literals && bytes[i] == b'\'' {
+            i = char_literal_end(bytes, i);
+            continue;
+        }
+
+        if let Some((quote, escapes)) = syntax
+            .quotes
+            .iter()
+            .find(|(quote, _)| rest.starts_with(quote.as_bytes()))
+            && (!syntax.scalar_quotes || at_value_start(bytes, i))
+        {
+            i = string_end(bytes, i + quote.len(), quote.as_bytes(), *escapes);
+            continue;
+        }
+
+        i += 1;
+    }
+
+    comments
+}
+
+fn is_doc(text: &str, syntax: &Syntax) -> bool {
+    // `////` 和 `/**/` 在 Rust 中都不是文档注释
+    if text.starts_with("////") || text.starts_with("/**/") {
+        return false;
+    }
+    syntax.doc.iter().any(|prefix| text.starts_with(prefix))
+}
+
+fn block_end(bytes: &[u8], mut i: usize, open: &str, close: &str, nested: bool) -> usize {
+    let mut depth = 1;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(close.as_bytes()) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return i;
+            }
+        } else if nested && rest.starts_with(open.as_bytes()) {
+            depth += 1;
+            i += open.len();
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+fn string_end(bytes: &[u8], mut i: usize, quote: &[u8], escapes: bool) -> usize {
+    while i < bytes.len() {
+        if escapes && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(quote) {
+            return i + quote.len();
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// 识别 `r"..."`、`r#"..."#`、`br"..."`，返回结束位置
+fn raw_string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    if bytes[start] != b'r' {
+        return None;
+    }
+    if start > 0 {
+        let prev = bytes[start - 1];
+        let prefix_ok = prev == b'b' && (start < 2 || !is_ident_byte(bytes[start - 2]));
+        if is_ident_byte(prev) && !prefix_ok {
+            return None;
+        }
+    }
+
+    let mut i = start + 1;
+    let mut hashes = 0;
+    while bytes.get(i) == Some(&b'#') {
+        hashes += 1;
+        i += 1;
+    }
+    if bytes.get(i) != Some(&b'"') {
+        return None;
+    }
+    i += 1;
+
+    while i < bytes.len() {
+        if bytes[i] == b'"'
+            && bytes.len() >= i + 1 + hashes
+            && bytes[i + 1..i + 1 + hashes].iter().all(|&b| b == b'#')
+        {
+            return Some(i + 1 + hashes);
+        }
+        i += 1;
+    }
+    Some(bytes.len())
+}
+
+/// 区分字符字面量与生命周期：`'a'`、`'\n'` 为字面量，`'a` 为生命周期
+fn char_literal_end(bytes: &[u8], start: usize) -> usize {
+    let i = start + 1;
+    if bytes.get(i) == Some(&b'\\') {
+        return bytes[i..]
+            .iter()
+            .take(12)
+            .position(|&b| b == b'\'')
+            .map_or(i, |p| i + p + 1);
+    }
+    // 跳过一个完整的 UTF-8 字符
+    let width = match bytes.get(i) {
+        Some(&b) if b >= 0xF0 => 4,
+        Some(&b) if b >= 0xE0 => 3,
+        Some(&b) if b >= 0xC0 => 2,
+        Some(_) => 1,
+        None => return i,
+    };
+    if bytes.get(i + width) == Some(&b'\'') {
+        i + width + 1
+    } else {
+        i
+    }
+}
+
+/// 本行中 `i` 之前为行首、流集合的 `[`、`{`、`,`，或后跟空白的 `:`、`-`、`?`
+fn at_value_start(bytes: &[u8], i: usize) -> bool {
+    let Some(j) = bytes[..i].iter().rposition(|&b| b != b' ' && b != b'\t') else {
+        return true;
+    };
+    match bytes[j] {
+        b'\n' | b'\r' | b'[' | b'{' | b',' => true,
+        b':' | b'-' | b'?' => j + 1 < i,
+        _ => false,
+    }
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 行分类
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 代码行、注释行、空行计数；同时含代码和注释的行计为代码
+#[derive(Clone, Copy, Default)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+impl LineCounts {
+    pub fn total(&self) -> usize {
+        self.code + self.comment + self.blank
+    }
+}
+
+impl AddAssign for LineCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
+}
+
+impl SubAssign for LineCounts {
+    fn sub_assign(&mut self, other: Self) {
+        self.code -= other.code;
+        self.comment -= other.comment;
+        self.blank -= other.blank;
+    }
+}
+
+/// 按语言统计代码、注释、空行
+pub fn count_lines(content: &str, lang: &str) -> LineCounts {
+    let comments = syntax_for(lang)
+        .map(|syntax| scan_comments(content, syntax))
+        .unwrap_or_default();
+
+    let mut counts = LineCounts::default();
+    let mut next_comment = 0;
+    let mut line_start = 0;
+
+    for line in content.lines() {
+        let start = line_start;
+        // `lines()` 会去掉 `\n` 和 `\r\n`，这里按原始位置推进
+        line_start += line.len();
+        if content.as_bytes().get(line_start) == Some(&b'\r') {
+            line_start += 1;
+        }
+        line_start += 1;
+
+        if line.trim().is_empty() {
+            counts.blank += 1;
+            continue;
+        }
+
+        let has_code =
+            line.char_indices()
+                .filter(|(_, c)| !c.is_whitespace())
+                .any(|(offset, _)| {
+                    let pos = start + offset;
+                    while next_comment < comments.len() && comments[next_comment].end <= pos {
+                        next_comment += 1;
+                    }
+                    comments.get(next_comment).is_none_or(|c| pos < c.start)
+                });
+
+        if has_code {
+            counts.code += 1;
+        } else {
+            counts.comment += 1;
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_apostrophe_in_plain_scalar_is_not_a_quote() {
+        let content =
+            "title: Don't panic\n# comment\nname: 'quoted # not a comment'\nid: a-'b\n# end\n";
+        let counts = count_lines(content, "yaml");
+        assert_eq!((counts.code, counts.comment), (3, 2));
+    }
+
+    #[test]
+    fn shell_hash_inside_word_is_not_a_comment() {
+        let content = "echo ${#x} $# a#b  # count\n";
+        let comments = scan_comments(content, syntax_for("bash").unwrap());
+        assert_eq!(comments.len(), 1);
+        assert_eq!(&content[comments[0].start..comments[0].end], "# count");
+    }
+}
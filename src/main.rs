@@ -1,8 +1,44 @@
-use chrono::Local;
+mod apply;
+mod attributes;
+mod bundle;
+mod cache;
+mod diff;
+mod encoding;
+mod generated;
+mod graph;
+mod imports;
+mod lang;
+mod loc;
+mod manifest;
+mod normalize;
+mod order;
+mod outline;
+mod prompt;
+mod rank;
+mod secrets;
+mod template;
+mod transform;
+mod truncate;
+mod unpack;
+mod watch;
+
+use attributes::Attributes;
+use cache::{DiskCache, FileInfo};
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use encoding_rs::Encoding;
+use graph::{Graph, GraphFormat};
+use loc::LineCounts;
+use manifest::Manifest;
+use normalize::{LineEndings, NormalizeOptions};
+use order::Order;
 use std::collections::{HashMap, HashSet};
-use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use template::Template;
+use transform::TransformOptions;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
 
 // ═══════════════════════════════════════════════════════════════════════════
 // 常量定义
@@ -40,6 +76,36 @@ const IGNORED_DIRS: &[&str] = &[
     "coverage",
 ];
 
+// 默认不收集的敏感文件（通配模式，含 `/` 时匹配相对路径，否则匹配文件名）
+const SENSITIVE_FILES: &[&str] = &[
+    ".env",
+    ".env.*",
+    "*.env",
+    "id_rsa*",
+    "id_dsa*",
+    "id_ecdsa*",
+    "id_ed25519*",
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "*.jks",
+    "*.keystore",
+    ".npmrc",
+    ".pypirc",
+    ".netrc",
+    ".git-credentials",
+    "kubeconfig",
+    "*.kubeconfig",
+    "**/.kube/config",
+    "terraform.tfstate",
+    "*.tfstate",
+    "*.tfstate.backup",
+];
+
+// 敏感模式中的例外（公钥、示例配置）
+const SENSITIVE_EXCEPTIONS: &[&str] = &["*.pub", "*.example", "*.sample", "*.template"];
+
 // ═══════════════════════════════════════════════════════════════════════════
 // 配置结构
 // ═══════════════════════════════════════════════════════════════════════════
@@ -49,12 +115,37 @@ struct Config {
     path: PathBuf,
     outfile: PathBuf,
     max_bytes: u64,
+    truncate: Option<(usize, usize)>, // 超过大小上限时保留的开头、结尾行数
     skip_exts: HashSet<String>,
     include_exts: Option<HashSet<String>>, // 白名单过滤
     ignore_dirs: HashSet<String>,          // 忽略的目录名
     ignore_files: HashSet<String>,         // 忽略的文件名
     show_tree: bool,
     show_toc: bool,
+    transform: TransformOptions,           // 写入前的内容精简
+    normalize: NormalizeOptions,           // 写入前的换行、制表符与 BOM 规范化
+    outline: bool,                         // 只输出声明骨架
+    line_numbers: bool,                    // 代码块每行加行号前缀
+    outline_keep: Vec<String>,             // 大纲模式下仍输出全文的文件
+    entries: Vec<String>,                  // 入口文件，只收集其可达文件
+    entry_depth: Option<usize>,            // 入口遍历的最大层数
+    graph: Option<GraphFormat>,            // 依赖图输出格式
+    graph_collapse: Option<usize>,         // 依赖图折叠到的目录层数
+    order: Order,                          // 目录和内容中文件的顺序
+    priority: Vec<String>,                 // 优先级排序使用的模式
+    query: Option<String>,                 // 按相关度打分的查询
+    max_tokens: Option<usize>,             // 文件内容的 token 预算
+    scan_secrets: bool,                    // 扫描并脱敏密钥
+    fail_on_secrets: bool,                 // 发现密钥时以非零状态退出
+    unsafe_include_sensitive: Vec<String>, // 明确放行的敏感文件
+    watch: bool,                           // 监视文件变化并自动重新生成
+    use_cache: bool,                       // 使用磁盘缓存复用未变化文件的统计结果
+    reproducible: bool,                    // 可复现输出：固定时间戳，源目录只写目录名
+    template: Option<PathBuf>,             // 自定义报告模板
+    prompt_prefix: Option<String>,         // 报告前的提示词（文件、片段名或文本）
+    prompt_suffix: Option<String>,         // 报告后的提示词（文件、片段名或文本）
+    include_generated: bool,               // 收集生成文件和压缩文件
+    encodings: Vec<&'static Encoding>,     // 非 UTF-8 文件的候选编码
 }
 
 impl Default for Config {
@@ -63,12 +154,37 @@ impl Default for Config {
             path: PathBuf::new(),
             outfile: PathBuf::from("all-in-one.md"),
             max_bytes: 1024 * 1024, // 1MB
+            truncate: None,
             skip_exts: HashSet::new(),
             include_exts: None,
             ignore_dirs: HashSet::new(),
             ignore_files: HashSet::new(),
             show_tree: true,
             show_toc: true,
+            transform: TransformOptions::default(),
+            normalize: NormalizeOptions::default(),
+            outline: false,
+            line_numbers: false,
+            outline_keep: Vec::new(),
+            entries: Vec::new(),
+            entry_depth: None,
+            graph: None,
+            graph_collapse: None,
+            order: Order::default(),
+            priority: Vec::new(),
+            query: None,
+            max_tokens: None,
+            scan_secrets: true,
+            fail_on_secrets: false,
+            unsafe_include_sensitive: Vec::new(),
+            watch: false,
+            use_cache: true,
+            reproducible: false,
+            template: None,
+            prompt_prefix: None,
+            prompt_suffix: None,
+            include_generated: false,
+            encodings: Vec::new(),
         }
     }
 }
@@ -77,17 +193,132 @@ impl Default for Config {
 // 统计信息
 // ═══════════════════════════════════════════════════════════════════════════
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct Stats {
     files_processed: usize,
     files_skipped_binary: usize,
     files_skipped_size: usize,
     files_skipped_encoding: usize,
     files_skipped_filter: usize,
+    files_skipped_generated: usize,
+    files_skipped_vendored: usize,
+    files_skipped_minified: usize,
+    files_transcoded: usize, // 从其他编码转为 UTF-8 的文件
+    files_withheld: usize,
+    sensitive_included: Vec<String>, // 通过 -UnsafeIncludeSensitive 放行的文件
+    skipped: Vec<Skipped>,           // 未收集的文件（不含忽略目录中的文件）
+    altered: HashMap<String, Vec<&'static str>>, // 写入内容被改写的文件及改写方式
     total_bytes: u64,
     total_lines: usize,
+    total_tokens: usize, // 含提示词
+    prompt_tokens: usize,
+    lines: LineCounts,
     dirs_count: usize,
+    files_outlined: usize,
+    truncated: Vec<(String, usize)>, // 被截断的文件及省略的行数
+    excluded: Vec<(String, usize, Option<f64>)>, // 超出 token 预算的文件、token 数和相关度
+    files_normalized: usize,
+    secret_findings: Vec<(String, secrets::Finding)>,
+    bytes_saved: u64,
+    tokens_saved: usize,
     file_types: HashMap<String, usize>,
+    languages: HashMap<String, LanguageStats>,
+}
+
+impl Stats {
+    /// 撤销一个已收集文件的统计（超出 token 预算时）
+    fn forget(&mut self, entry: &FileEntry) {
+        self.files_processed -= 1;
+        self.total_bytes -= entry.size;
+        self.total_lines -= entry.line_count;
+        self.total_tokens -= entry.tokens;
+        self.lines -= entry.lines;
+        if entry.encoding.is_some() {
+            self.files_transcoded -= 1;
+        }
+        if let Some(language) = self.languages.get_mut(&entry.language) {
+            language.files -= 1;
+            language.lines -= entry.lines;
+            if language.files == 0 {
+                self.languages.remove(&entry.language);
+            }
+        }
+        if let Some(count) = self.file_types.get_mut(&entry.extension) {
+            *count -= 1;
+            if *count == 0 {
+                self.file_types.remove(&entry.extension);
+            }
+        }
+    }
+}
+
+/// 文件未被收集的原因，写入清单
+#[derive(Clone, Copy)]
+enum SkipReason {
+    Filtered,
+    Sensitive,
+    Binary,
+    TooLarge,
+    Encoding,
+    Generated,
+    Minified,
+    Vendored,
+    ExportIgnore,
+    Budget,
+}
+
+impl SkipReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Filtered => "filtered",
+            Self::Sensitive => "sensitive",
+            Self::Binary => "binary",
+            Self::TooLarge => "too-large",
+            Self::Encoding => "encoding",
+            Self::Generated => "generated",
+            Self::Minified => "minified",
+            Self::Vendored => "vendored",
+            Self::ExportIgnore => "export-ignore",
+            Self::Budget => "budget",
+        }
+    }
+}
+
+/// 未收集的文件，写入清单的 `skipped`
+#[derive(Clone)]
+struct Skipped {
+    path: String,
+    reason: SkipReason,
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+impl Skipped {
+    /// 扫描时记录一次：大小取自元数据；敏感、二进制和过大的文件不读取内容、不记录哈希，
+    /// 以免泄露敏感文件的指纹或反复读取大文件
+    fn new(path: &Path, relative_path: String, reason: SkipReason, max_bytes: u64) -> Self {
+        let size = fs::metadata(path).ok().map(|m| m.len());
+        let hashed = !matches!(
+            reason,
+            SkipReason::Sensitive | SkipReason::Binary | SkipReason::TooLarge
+        ) && size.is_some_and(|size| size <= max_bytes);
+        Self {
+            path: relative_path,
+            reason,
+            size,
+            sha256: hashed
+                .then(|| fs::read(path).ok())
+                .flatten()
+                .map(|bytes| cache::hash_bytes(&bytes)),
+        }
+    }
+}
+
+/// 单门语言的汇总
+#[derive(Clone, Default)]
+struct LanguageStats {
+    files: usize,
+    lines: LineCounts,
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -97,8 +328,240 @@ struct Stats {
 struct FileEntry {
     relative_path: String,
     extension: String,
+    language: String, // 按文件名、shebang、模式行或扩展名识别
     size: u64,
     line_count: usize,
+    lines: LineCounts,
+    tokens: usize,
+    hash: String,                   // 原文件内容（解码为 UTF-8 后）的 SHA-256
+    encoding: Option<&'static str>, // 原始编码；UTF-8 为 `None`
+    truncated: bool,                // 超过大小上限，写入时截断
+    modified: Option<SystemTime>,
+    score: Option<f64>, // 与查询的相关度（`-Query`）
+    tree_index: usize,  // 目录树中对应的行
+}
+
+/// 写入报告的文件内容（经过规范化、大纲、精简和脱敏处理）
+struct RenderedFile<'a> {
+    entry: &'a FileEntry,
+    content: String,
+    line_endings: Option<LineEndings>, // 原文件的换行方式（纯 LF 时为 `None`）
+    normalized: bool,
+    omitted: usize, // 截断时省略的行数
+    outlined: bool,
+    trimmed: bool,
+    redactions: usize,
+}
+
+impl RenderedFile<'_> {
+    /// 围栏比内容中最长的反引号序列多一个，至少 3 个
+    fn fence(&self) -> String {
+        "`".repeat((count_max_backticks(&self.content) + 1).max(3))
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 扫描过滤条件
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Filters {
+    skip_exts: HashSet<String>,
+    reachable: Option<HashSet<String>>, // 入口可达的文件（相对路径）
+    outputs: Vec<PathBuf>,              // 本工具生成的文件（规范化路径），不收集
+}
+
+impl Filters {
+    fn is_output(&self, path: &Path) -> bool {
+        let matches_name = self
+            .outputs
+            .iter()
+            .any(|o| o.file_name() == path.file_name());
+        matches_name && fs::canonicalize(path).is_ok_and(|p| self.outputs.contains(&p))
+    }
+
+    /// 目录下是否可能有需要收集的文件
+    fn allows_dir(&self, relative_dir: &str) -> bool {
+        match &self.reachable {
+            Some(reachable) => {
+                relative_dir.is_empty()
+                    || reachable.iter().any(|p| {
+                        p.strip_prefix(relative_dir)
+                            .is_some_and(|rest| rest.starts_with('/'))
+                    })
+            }
+            None => true,
+        }
+    }
+
+    fn allows_file(&self, relative_path: &str) -> bool {
+        self.reachable
+            .as_ref()
+            .is_none_or(|reachable| reachable.contains(relative_path))
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 扫描结果
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Default)]
+struct ScanResult {
+    stats: Stats,
+    entries: Vec<FileEntry>,
+    tree: Vec<String>,
+    cache: ContentCache,
+    attributes: Attributes, // 已读取的 .gitattributes 规则
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 内容缓存（监视模式下跨轮次复用，统计结果另存磁盘）
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct CachedContent {
+    size: u64,
+    modified: Option<SystemTime>,
+    content: Option<String>,
+    encoding: Option<&'static str>,
+}
+
+/// 已读取的文件内容；大小和修改时间未变的文件不再重新读取
+#[derive(Default)]
+struct ContentCache {
+    files: HashMap<String, CachedContent>,
+    seen: HashSet<String>,
+    added: Vec<String>,
+    modified: Vec<String>,
+    disk: DiskCache,
+    keep_contents: bool, // 监视模式下把内容留在内存，避免每轮重新读取
+    encodings: Vec<&'static Encoding>, // 非 UTF-8 文件的候选编码
+}
+
+/// 一轮扫描相对上一轮的变化
+struct Changes {
+    added: Vec<String>,
+    modified: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl ContentCache {
+    fn new(disk: DiskCache, keep_contents: bool, encodings: Vec<&'static Encoding>) -> Self {
+        Self {
+            disk,
+            keep_contents,
+            encodings,
+            ..Default::default()
+        }
+    }
+
+    /// 文件的统计结果：磁盘缓存命中时不读取内容，否则读取、识别语言并重新统计
+    ///
+    /// `language` 为 `.gitattributes` 指定的语言，与识别结果不同时按它重新统计行数
+    fn file_info(
+        &mut self,
+        path: &Path,
+        relative_path: &str,
+        metadata: &fs::Metadata,
+        language: Option<&str>,
+    ) -> io::Result<FileInfo> {
+        self.touch(relative_path, metadata);
+        let mut info = match self.disk.lookup(relative_path, metadata) {
+            Some(info) if !self.keep_contents || self.get(relative_path).is_some() => info,
+            _ => {
+                self.read(path, relative_path)?;
+                let cached = &self.files[relative_path];
+                let content = cached.content.as_deref().unwrap_or_default();
+                let detected = lang::detect(relative_path, content);
+                self.disk
+                    .resolve(relative_path, metadata, detected, content, cached.encoding)
+            }
+        };
+
+        if let Some(language) = language
+            && language != info.language
+        {
+            let content = self.read(path, relative_path)?;
+            info.lines = loc::count_lines(content, language);
+            info.language = language.to_string();
+        }
+        Ok(info)
+    }
+
+    /// 登记本轮扫描到的文件并记录变化；文件已变化时丢弃旧内容
+    fn touch(&mut self, relative_path: &str, metadata: &fs::Metadata) {
+        self.seen.insert(relative_path.to_string());
+        let modified = metadata.modified().ok();
+        match self.files.get_mut(relative_path) {
+            Some(cached) if cached.size == metadata.len() && cached.modified == modified => {}
+            Some(cached) => {
+                *cached = CachedContent {
+                    size: metadata.len(),
+                    modified,
+                    content: None,
+                    encoding: None,
+                };
+                self.modified.push(relative_path.to_string());
+            }
+            None => {
+                self.files.insert(
+                    relative_path.to_string(),
+                    CachedContent {
+                        size: metadata.len(),
+                        modified,
+                        content: None,
+                        encoding: None,
+                    },
+                );
+                self.added.push(relative_path.to_string());
+            }
+        }
+    }
+
+    /// 读取已登记的文件并解码为 UTF-8，内容未缓存时才访问磁盘
+    fn read(&mut self, path: &Path, relative_path: &str) -> io::Result<&str> {
+        let cached = self
+            .files
+            .get_mut(relative_path)
+            .expect("读取前必须先调用 touch");
+        if cached.content.is_none() {
+            let decoded = encoding::read(path, &self.encodings)?;
+            cached.content = Some(decoded.text);
+            cached.encoding = decoded.encoding;
+        }
+        Ok(cached.content.as_deref().unwrap_or_default())
+    }
+
+    fn get(&self, relative_path: &str) -> Option<&str> {
+        self.files.get(relative_path)?.content.as_deref()
+    }
+
+    /// 解码后的内容；未缓存时按候选编码读取，无法读取或解码时为空
+    fn decoded(&self, base: &Path, relative_path: &str) -> String {
+        match self.get(relative_path) {
+            Some(content) => content.to_string(),
+            None => encoding::read(&base.join(relative_path), &self.encodings)
+                .map(|decoded| decoded.text)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 结束一轮扫描：清理本轮未出现的文件，返回变化列表
+    fn finish_round(&mut self) -> Changes {
+        let seen = std::mem::take(&mut self.seen);
+        let mut removed: Vec<String> = self
+            .files
+            .keys()
+            .filter(|path| !seen.contains(*path))
+            .cloned()
+            .collect();
+        removed.sort();
+        self.files.retain(|path, _| seen.contains(path));
+
+        Changes {
+            added: std::mem::take(&mut self.added),
+            modified: std::mem::take(&mut self.modified),
+            removed,
+        }
+    }
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -167,6 +630,61 @@ fn get_language(ext: &str) -> &'static str {
 // 辅助函数
 // ═══════════════════════════════════════════════════════════════════════════
 
+/// 通配匹配：`*` 不跨目录，`**` 跨目录，`?` 匹配单个字符
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[u8], t: &[u8]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some(b'*') if p.get(1) == Some(&b'*') => {
+                let rest = &p[2..];
+                // `**/` 也可以匹配零层目录
+                if let Some(after_slash) = rest.strip_prefix(b"/")
+                    && matches(after_slash, t)
+                {
+                    return true;
+                }
+                (0..=t.len()).any(|i| matches(rest, &t[i..]))
+            }
+            Some(b'*') => {
+                let rest = &p[1..];
+                (0..=t.len())
+                    .take_while(|&i| i == 0 || t[i - 1] != b'/')
+                    .any(|i| matches(rest, &t[i..]))
+            }
+            Some(b'?') => !t.is_empty() && t[0] != b'/' && matches(&p[1..], &t[1..]),
+            Some(&c) => t.first() == Some(&c) && matches(&p[1..], &t[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// 按文件名或相对路径匹配通配模式
+fn path_matches(pattern: &str, file_name: &str, relative_path: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern, relative_path)
+    } else {
+        glob_match(pattern, file_name)
+    }
+}
+
+/// 是否属于默认不收集的敏感文件
+fn is_sensitive(path: &Path, file_name: &str, relative_path: &str) -> bool {
+    let matched = SENSITIVE_FILES
+        .iter()
+        .any(|p| path_matches(p, file_name, relative_path))
+        && !SENSITIVE_EXCEPTIONS
+            .iter()
+            .any(|p| path_matches(p, file_name, relative_path));
+
+    // `.npmrc` 只有包含令牌时才算敏感
+    if matched && file_name == ".npmrc" {
+        return fs::read_to_string(path).map_or(true, |content| {
+            content.contains("_auth") || content.contains("_password")
+        });
+    }
+    matched
+}
+
 /// 计算内容中最长的连续反引号数量
 fn count_max_backticks(content: &str) -> usize {
     let mut max_count = 0;
@@ -184,6 +702,18 @@ fn count_max_backticks(content: &str) -> usize {
     max_count
 }
 
+/// 文件名排序键：先忽略大小写和附加符号（`é` 排在 `e` 旁边），再按 NFC 规范化后的名称区分
+///
+/// 不同文件系统返回的文件名可能是 NFC 或 NFD，直接比较字节会导致顺序随平台变化
+fn sort_key(name: &str) -> (String, String) {
+    let base: String = name
+        .nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(char::to_lowercase)
+        .collect();
+    (base, name.nfc().collect())
+}
+
 fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
@@ -200,6 +730,13 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
+/// 粗略估算 token 数：ASCII 约 4 字节一个 token，其他字符（如中文）按一个字符一个 token
+fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.bytes().filter(|b| b.is_ascii()).count();
+    let other = text.chars().filter(|c| !c.is_ascii()).count();
+    ascii.div_ceil(4) + other
+}
+
 fn print_colored(color: &str, symbol: &str, message: &str) {
     let color_code = match color {
         "green" => "\x1b[32m",
@@ -218,6 +755,11 @@ fn print_progress(current: usize, message: &str) {
     io::stdout().flush().ok();
 }
 
+fn clear_progress() {
+    print!("\r\x1b[K");
+    io::stdout().flush().ok();
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 // 帮助信息
 // ═══════════════════════════════════════════════════════════════════════════
@@ -231,17 +773,65 @@ fn print_help() {
 用法:
   code_collector                           # 交互模式
   code_collector -Path <目录> [选项]       # 命令行模式
+  code_collector <子命令> [参数]           # 子命令，详见 <子命令> -h
+
+子命令:
+  unpack <文件>        把生成的 Markdown 还原为目录（-Path 目标目录，-DryRun 预演，-Force 覆盖）
+  apply <文件>         把修改后的 Markdown 或统一 diff 写回目录，显示差异并确认（-Yes 跳过确认）
+  diff <旧> <新>       比较两个包或包与目录，列出增删改及行数变化（-OutFile 另存变更包）
 
 选项:
   -Path <路径>         目标目录路径（必需）
   -OutFile <文件>      输出文件名（默认: all-in-one.md）
   -MaxBytes <大小>     最大文件大小（默认: 1048576 = 1MB）
+  -Truncate <行数>     超过大小上限的文件不跳过，只保留开头和结尾的行，
+                       如 "200 50" 为前 200 行和后 50 行，只写一个数时首尾相同
+                       截断后仍超过上限（只有几行超长的行）时照常跳过
   -SkipExts <扩展名>   跳过的扩展名（空格分隔）
   -IncludeExts <扩展名> 只包含的扩展名（空格分隔，白名单模式）
   -IgnoreDirs <名称>   忽略的特定目录名（空格分隔，如: tests docs）
   -IgnoreFiles <名称>  忽略的特定文件名（空格分隔，如: package-lock.json）
   -NoTree              不生成目录树
   -NoToc               不生成目录索引
+  -StripComments       删除注释
+  -KeepDocComments     删除注释时保留文档注释
+  -CollapseBlankLines  合并连续空行
+  -TrimTrailing        删除行尾空白
+  -StripLicense        删除文件开头的许可证头部
+  -NormalizeLineEndings 把 CRLF 和 CR 换行统一为 LF
+  -ExpandTabs <宽度>   把制表符展开为空格，按给定宽度对齐制表位
+  -StripBom            删除 UTF-8 BOM
+  -Outline             大纲模式：只输出声明和函数签名（Rust/Python/TypeScript/Go/Java）
+  -LineNumbers         代码块每行加行号前缀，便于按行引用（unpack/apply 会自动去掉）
+  -OutlineKeep <路径>  大纲模式下仍输出全文的文件或目录（空格分隔）
+  -Entry <文件>        入口文件（空格分隔），只收集沿 mod/use/import/require 可达的文件
+  -EntryDepth <层数>   入口遍历的最大层数（默认不限）
+  -Graph <格式>        输出模块依赖图：mermaid（写入 Markdown）或 dot（单独的 .dot 文件）
+  -GraphCollapse <层数> 依赖图按前 N 层目录折叠节点
+  -Order <方式>        目录和内容中文件的顺序：tree（与目录树相同，默认）、
+                       priority（说明文档、项目清单和入口文件在前，测试在后）、
+                       size（从小到大）、dependency（被依赖的在前）、
+                       recent（最近修改的在前）、name（按完整路径）
+  -Priority <模式>     priority 排序的优先级模式（空格分隔，按先后排列，忽略大小写）
+  -Query <文本>        按与查询的相关度给文件打分（路径和标识符的 BM25、导入关系、修改时间）
+  -MaxTokens <数量>    整份报告的 token 预算；按处理后（大纲、精简等）的内容计算，
+                       有 -Query 时按相关度从高到低装入，否则按 -Order 的顺序，
+                       放不下的文件列在报告中
+  -IncludeGenerated    收集生成文件和压缩文件（默认只在目录树中标记）
+  -Encodings <列表>    非 UTF-8 文件的候选编码，如 "gbk shift_jis"（默认自动检测）
+  -NoSecretScan        不扫描和脱敏密钥（默认扫描）
+  -FailOnSecrets       发现疑似密钥时以非零状态退出（监视模式下停止监视）
+  -Watch               监视目录变化，自动重新生成
+  -NoCache             不使用磁盘缓存，重新统计全部文件
+  -ClearCache          删除全部缓存后退出
+  -PruneCache          删除已不存在目录的缓存后退出
+  -Template <文件>     使用自定义报告模板（Jinja 语法，上下文变量见 README）
+  -PromptPrefix <值>   放在报告最前面的提示词：文件路径、片段名或直接写文本
+  -PromptSuffix <值>   放在报告最后面的提示词（如要问的问题），取值同上
+                       内置片段: review、explain、find-bugs、write-tests
+  -Reproducible        可复现输出：不写生成时间（或取 SOURCE_DATE_EPOCH），源目录只写目录名
+  -UnsafeIncludeSensitive <名称>
+                       放行默认不收集的敏感文件（.env、私钥、tfstate 等，空格分隔的文件名或通配模式）
   -h, --help           显示帮助信息
 
 示例:
@@ -249,6 +839,16 @@ fn print_help() {
   code_collector -Path ./src -OutFile code.md -MaxBytes 512000
   code_collector -Path . -IncludeExts "rs toml md"
   code_collector -Path ./project -IgnoreDirs "tests examples" -IgnoreFiles "cargo.lock"
+  code_collector -Path . -StripComments -KeepDocComments -CollapseBlankLines
+  code_collector -Path . -Outline -OutlineKeep "src/main.rs README.md"
+  code_collector -Path . -Entry src/main.rs -EntryDepth 2
+  code_collector -Path . -Graph mermaid -GraphCollapse 2
+  code_collector -Path ./src -Watch
+  code_collector -Path . -Template review.md.jinja
+  code_collector -Path . -PromptPrefix review -PromptSuffix "登录失败时为什么没有重试？"
+  code_collector unpack all-in-one.md -Path ./restored -DryRun
+  code_collector apply reply.md -Path . -Base all-in-one.md
+  code_collector diff last-week.md ./my_project -OutFile changes.md
 "#,
         VERSION
     );
@@ -393,6 +993,25 @@ fn parse_args() -> Result<Option<Config>, Box<dyn std::error::Error>> {
         std::process::exit(0);
     }
 
+    // 缓存维护命令，执行后直接退出
+    if args
+        .iter()
+        .any(|a| a == "-ClearCache" || a == "-PruneCache")
+    {
+        let removed = if args.iter().any(|a| a == "-ClearCache") {
+            cache::clear()?
+        } else {
+            cache::prune()?
+        };
+        let dir = cache::cache_dir().unwrap_or_default();
+        print_colored(
+            "green",
+            "✓",
+            &format!("已删除 {} 个缓存文件（{}）", removed, dir.display()),
+        );
+        std::process::exit(0);
+    }
+
     let mut config = Config::default();
     let mut i = 1;
 
@@ -410,6 +1029,20 @@ fn parse_args() -> Result<Option<Config>, Box<dyn std::error::Error>> {
                 i += 1;
                 config.max_bytes = args.get(i).ok_or("缺少 -MaxBytes 的值")?.parse()?;
             }
+            "-Truncate" => {
+                i += 1;
+                let counts: Vec<usize> = args
+                    .get(i)
+                    .ok_or("缺少 -Truncate 的值")?
+                    .split_whitespace()
+                    .map(|n| n.parse())
+                    .collect::<Result<_, _>>()?;
+                config.truncate = match counts[..] {
+                    [head] => Some((head, head)),
+                    [head, tail] => Some((head, tail)),
+                    _ => return Err("-Truncate 需要一个或两个行数".into()),
+                };
+            }
             "-SkipExts" => {
                 i += 1;
                 config.skip_exts = args
@@ -449,6 +1082,117 @@ fn parse_args() -> Result<Option<Config>, Box<dyn std::error::Error>> {
             }
             "-NoTree" => config.show_tree = false,
             "-NoToc" => config.show_toc = false,
+            "-StripComments" => config.transform.strip_comments = true,
+            "-KeepDocComments" => config.transform.keep_doc_comments = true,
+            "-CollapseBlankLines" => config.transform.collapse_blank_lines = true,
+            "-TrimTrailing" => config.transform.trim_trailing = true,
+            "-StripLicense" => config.transform.strip_license = true,
+            "-NormalizeLineEndings" => config.normalize.line_endings = true,
+            "-StripBom" => config.normalize.strip_bom = true,
+            "-ExpandTabs" => {
+                i += 1;
+                let width: usize = args.get(i).ok_or("缺少 -ExpandTabs 的值")?.parse()?;
+                if width == 0 {
+                    return Err("-ExpandTabs 须大于 0".into());
+                }
+                config.normalize.tab_width = Some(width);
+            }
+            "-Outline" => config.outline = true,
+            "-LineNumbers" => config.line_numbers = true,
+            "-Watch" => config.watch = true,
+            "-NoCache" => config.use_cache = false,
+            "-Reproducible" => config.reproducible = true,
+            "-IncludeGenerated" => config.include_generated = true,
+            "-Encodings" => {
+                i += 1;
+                config.encodings =
+                    encoding::parse_list(args.get(i).ok_or("缺少 -Encodings 的值")?)?;
+            }
+            "-PromptPrefix" => {
+                i += 1;
+                config.prompt_prefix = Some(args.get(i).ok_or("缺少 -PromptPrefix 的值")?.clone());
+            }
+            "-PromptSuffix" => {
+                i += 1;
+                config.prompt_suffix = Some(args.get(i).ok_or("缺少 -PromptSuffix 的值")?.clone());
+            }
+            "-Template" => {
+                i += 1;
+                config.template = Some(PathBuf::from(args.get(i).ok_or("缺少 -Template 的值")?));
+            }
+            "-NoSecretScan" => config.scan_secrets = false,
+            "-FailOnSecrets" => config.fail_on_secrets = true,
+            "-UnsafeIncludeSensitive" => {
+                i += 1;
+                config.unsafe_include_sensitive = args
+                    .get(i)
+                    .ok_or("缺少 -UnsafeIncludeSensitive 的值")?
+                    .split_whitespace()
+                    .map(|s| s.to_string())
+                    .collect();
+            }
+            "-Entry" => {
+                i += 1;
+                config.entries = args
+                    .get(i)
+                    .ok_or("缺少 -Entry 的值")?
+                    .split_whitespace()
+                    .map(|s| s.to_string())
+                    .collect();
+            }
+            "-Graph" => {
+                i += 1;
+                let value = args.get(i).ok_or("缺少 -Graph 的值")?;
+                config.graph =
+                    Some(GraphFormat::parse(value).ok_or_else(|| {
+                        format!("未知的依赖图格式: {}（可选 mermaid、dot）", value)
+                    })?);
+            }
+            "-Order" => {
+                i += 1;
+                let value = args.get(i).ok_or("缺少 -Order 的值")?;
+                config.order = Order::parse(value).ok_or_else(|| {
+                    format!(
+                        "未知的排序方式: {}（可选 tree、priority、size、dependency、recent、name）",
+                        value
+                    )
+                })?;
+            }
+            "-Query" => {
+                i += 1;
+                config.query = Some(args.get(i).ok_or("缺少 -Query 的值")?.clone());
+            }
+            "-MaxTokens" => {
+                i += 1;
+                config.max_tokens = Some(args.get(i).ok_or("缺少 -MaxTokens 的值")?.parse()?);
+            }
+            "-Priority" => {
+                i += 1;
+                config.priority = args
+                    .get(i)
+                    .ok_or("缺少 -Priority 的值")?
+                    .split_whitespace()
+                    .map(|s| s.to_string())
+                    .collect();
+            }
+            "-GraphCollapse" => {
+                i += 1;
+                config.graph_collapse =
+                    Some(args.get(i).ok_or("缺少 -GraphCollapse 的值")?.parse()?);
+            }
+            "-EntryDepth" => {
+                i += 1;
+                config.entry_depth = Some(args.get(i).ok_or("缺少 -EntryDepth 的值")?.parse()?);
+            }
+            "-OutlineKeep" => {
+                i += 1;
+                config.outline_keep = args
+                    .get(i)
+                    .ok_or("缺少 -OutlineKeep 的值")?
+                    .split_whitespace()
+                    .map(|s| s.trim_end_matches('/').to_string())
+                    .collect();
+            }
             arg => return Err(format!("未知参数: {}", arg).into()),
         }
         i += 1;
@@ -473,6 +1217,15 @@ fn main() {
 }
 
 fn run() -> Result<(), Box<dyn std::error::Error>> {
+    // 子命令
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("unpack") => return unpack::run(&args[2..]),
+        Some("apply") => return apply::run(&args[2..]),
+        Some("diff") => return diff::run(&args[2..]),
+        _ => {}
+    }
+
     let config = match parse_args()? {
         Some(c) => c,
         None => interactive_input()?,
@@ -486,81 +1239,387 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         return Err(format!("路径不是目录: {}", config.path.display()).into());
     }
 
+    // 确定输出路径
+    let outfile_path = if config.outfile.is_relative() {
+        std::env::current_dir()?.join(&config.outfile)
+    } else {
+        config.outfile.clone()
+    };
+
+    let disk = if config.use_cache {
+        DiskCache::open(&config.path)
+    } else {
+        DiskCache::default()
+    };
+    let mut cache = ContentCache::new(disk, config.watch, config.encodings.clone());
+    let (stats, _) = generate(&config, &outfile_path, &mut cache, false)?;
+    print_summary(&config, &stats, &outfile_path);
+
+    check_secrets(&config, &stats)?;
+    if config.watch {
+        return watch_and_rebuild(&config, &outfile_path, &mut cache);
+    }
+
+    Ok(())
+}
+
+/// `-FailOnSecrets`：发现密钥时返回错误
+fn check_secrets(config: &Config, stats: &Stats) -> Result<(), Box<dyn std::error::Error>> {
+    if config.fail_on_secrets && !stats.secret_findings.is_empty() {
+        return Err(format!(
+            "发现 {} 处疑似密钥（-FailOnSecrets）",
+            stats.secret_findings.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// 扫描目录并写出 Markdown；`quiet` 为真时不输出阶段提示
+fn generate(
+    config: &Config,
+    outfile_path: &Path,
+    cache: &mut ContentCache,
+    quiet: bool,
+) -> Result<(Stats, Changes), Box<dyn std::error::Error>> {
+    let say = |color: &str, symbol: &str, message: &str| {
+        if !quiet {
+            print_colored(color, symbol, message);
+        }
+    };
+
+    // 读取模板（监视模式下每轮重新读取，便于调整模板）
+    let template = match &config.template {
+        Some(path) => {
+            let source = fs::read_to_string(path)
+                .map_err(|e| format!("无法读取模板 {}: {}", path.display(), e))?;
+            Template::compile(&path.display().to_string(), source)
+        }
+        None => Template::compile("default.md.jinja", template::DEFAULT.to_string()),
+    }
+    .map_err(|e| format!("模板有误: {:#}", e))?;
+
+    // 解析提示词
+    let prefix = config
+        .prompt_prefix
+        .as_deref()
+        .map(prompt::resolve)
+        .transpose()?;
+    let suffix = config
+        .prompt_suffix
+        .as_deref()
+        .map(prompt::resolve)
+        .transpose()?;
+
     // 构建跳过扩展名集合
-    let mut skip_set: HashSet<String> = BINARY_EXTS.iter().map(|&s| s.to_string()).collect();
-    skip_set.extend(config.skip_exts.clone());
+    let mut skip_exts: HashSet<String> = BINARY_EXTS.iter().map(|&s| s.to_string()).collect();
+    skip_exts.extend(config.skip_exts.clone());
 
-    // 第一遍：收集文件信息
-    print_colored("blue", "→", "正在扫描文件...");
-    let mut stats = Stats::default();
-    let mut entries: Vec<FileEntry> = Vec::new();
-    let mut dir_tree: Vec<String> = Vec::new();
-
-    collect_files(
-        &config.path,
-        &config.path,
-        &skip_set,
-        &config.ignore_dirs,
-        &config.ignore_files,
-        &config.include_exts,
-        config.max_bytes,
-        &mut stats,
-        &mut entries,
-        &mut dir_tree,
-        0,
-    )?;
+    // 计算入口可达文件
+    let reachable = if config.entries.is_empty() {
+        None
+    } else {
+        let entries = resolve_entries(config)?;
+        let reachable = imports::reachable(
+            &config.path,
+            &entries,
+            config.entry_depth,
+            &config.encodings,
+            lang::detect,
+        );
+        say(
+            "blue",
+            "→",
+            &format!("从 {} 个入口可达 {} 个文件", entries.len(), reachable.len()),
+        );
+        Some(reachable)
+    };
 
-    println!();
-    print_colored(
+    let filters = Filters {
+        skip_exts,
+        reachable,
+        outputs: output_paths(outfile_path),
+    };
+
+    // 第一遍：收集文件信息
+    say("blue", "→", "正在扫描文件...");
+    let mut scan = ScanResult {
+        cache: std::mem::take(cache),
+        ..Default::default()
+    };
+    collect_files(&config.path, config, &filters, &mut scan, 0)?;
+    let ScanResult {
+        mut stats,
+        mut entries,
+        tree: mut dir_tree,
+        cache: scanned,
+        ..
+    } = scan;
+    *cache = scanned;
+    let changes = cache.finish_round();
+    order::sort(&mut entries, config, cache);
+
+    // 提示词与文件内容一起计入 token 估算
+    stats.prompt_tokens = [&prefix, &suffix]
+        .into_iter()
+        .flatten()
+        .map(|p| estimate_tokens(&p.text))
+        .sum();
+    stats.total_tokens += stats.prompt_tokens;
+
+    clear_progress();
+    say(
         "green",
         "✓",
         &format!("扫描完成，共 {} 个文件", entries.len()),
     );
 
-    // 确定输出路径
-    let outfile_path = if config.outfile.is_relative() {
-        std::env::current_dir()?.join(&config.outfile)
-    } else {
-        config.outfile.clone()
-    };
+    score_files(config, cache, &mut entries);
+    let generated = generated_at(config)?;
+
+    // 处理文件内容
+    say("blue", "→", "正在写入文件内容...");
+    let mut prepared = prepare_files(config, &entries, cache)?;
+    if !quiet {
+        println!();
+    }
+
+    // 按处理后的内容取舍 token 预算，模板和清单的开销一并扣除
+    if config.max_tokens.is_some() {
+        let mermaid = (config.graph == Some(GraphFormat::Mermaid))
+            .then(|| build_graph(config, cache, &entries).to_mermaid());
+        let render =
+            |stats: &Stats, tree: &[String], files: &[RenderedFile], entries: &[FileEntry]| {
+                let context =
+                    template::context(config, generated, stats, tree, mermaid.clone(), files);
+                let body = template
+                    .render(&context)
+                    .map_err(|e| format!("模板渲染失败: {:#}", e))?;
+                let manifest = Manifest::build(config, generated, stats, entries).render();
+                Ok(estimate_tokens(&body) + estimate_tokens(&manifest))
+            };
+        let overhead = Overhead::measure(render, &stats, &dir_tree, &entries, &prepared)?;
+        select_files(
+            config,
+            overhead,
+            &mut entries,
+            &mut prepared,
+            &mut stats,
+            &mut dir_tree,
+        );
+    }
+
+    // 依赖图：Mermaid 写入报告，DOT 单独成文件
+    let mut mermaid = None;
+    if let Some(format) = config.graph {
+        let graph = build_graph(config, cache, &entries);
+
+        match format {
+            GraphFormat::Mermaid => {
+                if !graph.is_empty() {
+                    mermaid = Some(graph.to_mermaid());
+                }
+            }
+            GraphFormat::Dot => {
+                let dot_path = outfile_path.with_extension("dot");
+                fs::write(&dot_path, graph.to_dot())?;
+                say("green", "✓", &format!("依赖图: {}", dot_path.display()));
+            }
+        }
+        say("blue", "→", &format!("依赖关系 {} 条", graph.edge_count()));
+    }
+
+    let files = render_files(&entries, prepared, &mut stats);
+
+    // 按模板渲染，成功后才覆盖输出文件
+    let context = template::context(config, generated, &stats, &dir_tree, mermaid, &files);
+    let body = template
+        .render(&context)
+        .map_err(|e| format!("模板渲染失败: {:#}", e))?;
+
+    // 提示词放在最外层：前缀在报告之前，后缀在清单之后，模型最后读到的是问题
+    let mut report = String::new();
+    if let Some(prefix) = &prefix {
+        report.push_str(prefix.text.trim_end());
+        report.push_str("\n\n");
+    }
+    report.push_str(&body);
+    report.push('\n');
+    report.push_str(&Manifest::build(config, generated, &stats, &entries).render());
+    if let Some(suffix) = &suffix {
+        report.push('\n');
+        report.push_str(suffix.text.trim_end());
+        report.push('\n');
+    }
 
-    // 创建输出文件（覆盖模式）
     if let Some(parent) = outfile_path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let file = File::create(&outfile_path)?;
-    let mut writer = BufWriter::new(file);
-
-    // 写入头部信息
-    write_header(&mut writer, &config, &stats, &entries)?;
+    fs::write(outfile_path, report)?;
 
-    // 写入目录树
-    if config.show_tree && !dir_tree.is_empty() {
-        write_tree(&mut writer, &dir_tree)?;
+    // 缓存只影响速度，写入失败不中断
+    if let Err(e) = cache.disk.save() {
+        print_colored("yellow", "⚠", &format!("缓存写入失败: {}", e));
     }
 
-    // 写入文件目录（TOC）
-    if config.show_toc && !entries.is_empty() {
-        write_toc(&mut writer, &entries)?;
+    Ok((stats, changes))
+}
+
+/// 收集到的文件之间的依赖图
+fn build_graph(config: &Config, cache: &ContentCache, entries: &[FileEntry]) -> Graph {
+    let contents: Vec<String> = entries
+        .iter()
+        .map(|e| cache.decoded(&config.path, &e.relative_path))
+        .collect();
+    let files: Vec<(&str, &str, &str)> = entries
+        .iter()
+        .zip(&contents)
+        .map(|(e, content)| {
+            (
+                e.relative_path.as_str(),
+                e.language.as_str(),
+                content.as_str(),
+            )
+        })
+        .collect();
+    Graph::build(&config.path, &files, config.graph_collapse)
+}
+
+/// 写入报告的生成时间
+///
+/// 设置了 `SOURCE_DATE_EPOCH` 时总是使用它（UTC）；可复现模式下未设置则不写时间
+fn generated_at(config: &Config) -> Result<Option<DateTime<FixedOffset>>, String> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .map(|time| Some(time.fixed_offset()))
+            .ok_or_else(|| format!("SOURCE_DATE_EPOCH 不是有效的 Unix 时间戳: {}", value)),
+        Err(_) if config.reproducible => Ok(None),
+        Err(_) => Ok(Some(Local::now().fixed_offset())),
     }
+}
 
-    // 写入文件内容
-    print_colored("blue", "→", "正在写入文件内容...");
-    write_files(&mut writer, &config.path, &entries)?;
+/// 目标目录的名称；`.`、`..` 等写法按规范化后的路径取名
+fn source_name(path: &Path) -> String {
+    fs::canonicalize(path)
+        .ok()
+        .as_deref()
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().nfc().collect())
+        .unwrap_or_else(|| "Project".to_string())
+}
 
-    // 写入统计信息
-    write_stats(&mut writer, &stats)?;
+/// 报告中显示的源目录：可复现模式下只写目录名，避免写入本机的绝对路径
+fn source_display(config: &Config) -> String {
+    if config.reproducible {
+        source_name(&config.path)
+    } else {
+        config.path.display().to_string()
+    }
+}
 
-    writer.flush()?;
+/// 输出 Markdown 及附带的 `.dot` 文件的规范化路径
+fn output_paths(outfile_path: &Path) -> Vec<PathBuf> {
+    [
+        outfile_path.to_path_buf(),
+        outfile_path.with_extension("dot"),
+    ]
+    .into_iter()
+    .map(|p| fs::canonicalize(&p).unwrap_or(p))
+    .collect()
+}
 
-    // 输出完成信息
+/// 按默认规则扫描目录，返回（相对路径, 内容），供 diff 子命令与包比较
+fn collect_contents(path: &Path) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    if !path.is_dir() {
+        return Err(format!("路径不是目录: {}", path.display()).into());
+    }
+    let config = Config {
+        path: path.to_path_buf(),
+        ..Default::default()
+    };
+    let filters = Filters {
+        skip_exts: BINARY_EXTS.iter().map(|&s| s.to_string()).collect(),
+        reachable: None,
+        outputs: Vec::new(),
+    };
+    let mut scan = ScanResult {
+        cache: ContentCache::new(DiskCache::default(), true, Vec::new()),
+        ..Default::default()
+    };
+    collect_files(&config.path, &config, &filters, &mut scan, 0)?;
+    clear_progress();
+
+    Ok(scan
+        .entries
+        .iter()
+        .filter_map(|e| {
+            let content = scan.cache.get(&e.relative_path)?;
+            Some((e.relative_path.clone(), content.to_string()))
+        })
+        .collect())
+}
+
+fn print_summary(config: &Config, stats: &Stats, outfile_path: &Path) {
     println!();
     print_colored("green", "✓", &format!("已生成: {}", outfile_path.display()));
     println!();
     println!("  📄 文件数: {}", stats.files_processed);
     println!("  📁 目录数: {}", stats.dirs_count);
-    println!("  📏 总行数: {}", stats.total_lines);
+    println!(
+        "  📏 总行数: {}（代码 {} / 注释 {} / 空行 {}）",
+        stats.total_lines, stats.lines.code, stats.lines.comment, stats.lines.blank
+    );
     println!("  💾 总大小: {}", format_size(stats.total_bytes));
+    if stats.prompt_tokens > 0 {
+        println!(
+            "  🔢 估算 tokens: {}（含提示词 {}）",
+            stats.total_tokens, stats.prompt_tokens
+        );
+    } else {
+        println!("  🔢 估算 tokens: {}", stats.total_tokens);
+    }
+    if stats.files_transcoded > 0 {
+        println!("  🔤 转为 UTF-8: {}", stats.files_transcoded);
+    }
+    if !stats.excluded.is_empty() {
+        print_colored(
+            "yellow",
+            "  ⚠",
+            &format!(
+                "超出 token 预算: {} 个文件（约 {} tokens）",
+                stats.excluded.len(),
+                stats
+                    .excluded
+                    .iter()
+                    .map(|(_, tokens, _)| tokens)
+                    .sum::<usize>()
+            ),
+        );
+    }
+    if !stats.truncated.is_empty() {
+        println!(
+            "  ✂ 截断: {} 个文件（省略 {} 行）",
+            stats.truncated.len(),
+            stats.truncated.iter().map(|(_, n)| n).sum::<usize>()
+        );
+    }
+    if stats.files_normalized > 0 {
+        println!("  🧹 已规范化: {}", stats.files_normalized);
+    }
+    if stats.files_outlined > 0 {
+        println!("  🧩 仅大纲: {}", stats.files_outlined);
+    }
+    if config.transform.is_enabled() {
+        println!(
+            "  ✂ 精简节省: {}（约 {} tokens）",
+            format_size(stats.bytes_saved),
+            stats.tokens_saved
+        );
+    }
     if stats.files_skipped_size > 0 {
         print_colored(
             "yellow",
@@ -575,39 +1634,188 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             &format!("跳过（二进制）: {}", stats.files_skipped_binary),
         );
     }
+    if stats.files_skipped_generated > 0 {
+        print_colored(
+            "gray",
+            "  ○",
+            &format!("跳过（生成文件）: {}", stats.files_skipped_generated),
+        );
+    }
+    if stats.files_skipped_minified > 0 {
+        print_colored(
+            "gray",
+            "  ○",
+            &format!("跳过（压缩文件）: {}", stats.files_skipped_minified),
+        );
+    }
+    if stats.files_skipped_vendored > 0 {
+        print_colored(
+            "gray",
+            "  ○",
+            &format!("跳过（第三方代码）: {}", stats.files_skipped_vendored),
+        );
+    }
+    if stats.files_withheld > 0 {
+        print_colored(
+            "yellow",
+            "  ⚠",
+            &format!(
+                "跳过（敏感文件）: {}，可用 -UnsafeIncludeSensitive 放行",
+                stats.files_withheld
+            ),
+        );
+    }
+    for path in &stats.sensitive_included {
+        print_colored("red", "  ⚠", &format!("已放行敏感文件: {}", path));
+    }
+    if !stats.secret_findings.is_empty() {
+        print_colored(
+            "red",
+            "  🔐",
+            &format!("发现疑似密钥 {} 处，已脱敏:", stats.secret_findings.len()),
+        );
+        for (path, finding) in &stats.secret_findings {
+            println!("     {}:{} [{}]", path, finding.line, finding.rule);
+        }
+    }
     println!();
+}
 
-    Ok(())
+// ═══════════════════════════════════════════════════════════════════════════
+// 监视模式
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn watch_and_rebuild(
+    config: &Config,
+    outfile_path: &Path,
+    cache: &mut ContentCache,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = fs::canonicalize(&config.path)?;
+    // 输出文件可能位于被监视的目录内，必须排除，否则会无限触发
+    let outputs = output_paths(outfile_path);
+
+    let is_ignored = |path: &Path| {
+        if outputs.iter().any(|o| o == path) {
+            return true;
+        }
+        let Ok(relative) = path.strip_prefix(&root) else {
+            return true;
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        config.ignore_files.contains(&file_name)
+            || relative.components().any(|c| {
+                let name = c.as_os_str().to_string_lossy();
+                IGNORED_DIRS.contains(&name.as_ref()) || config.ignore_dirs.contains(name.as_ref())
+            })
+    };
+
+    print_colored("cyan", "👀", "正在监视文件变化（Ctrl+C 退出）...");
+
+    // 重新生成后发现密钥且指定了 -FailOnSecrets 时停止监视并返回该错误
+    let mut failure = None;
+    watch::watch(&root, is_ignored, || {
+        let time = Local::now().format("%H:%M:%S");
+        match generate(config, outfile_path, cache, true) {
+            Ok((stats, changes)) => {
+                let mut parts = Vec::new();
+                for (label, paths) in [
+                    ("修改", &changes.modified),
+                    ("新增", &changes.added),
+                    ("删除", &changes.removed),
+                ] {
+                    if !paths.is_empty() {
+                        parts.push(format!("{} {}: {}", label, paths.len(), paths.join(", ")));
+                    }
+                }
+                if parts.is_empty() {
+                    parts.push("内容无变化".to_string());
+                }
+                print_colored(
+                    "green",
+                    "↻",
+                    &format!(
+                        "[{}] 已重新生成（{} 个文件）| {}",
+                        time,
+                        stats.files_processed,
+                        parts.join(" | ")
+                    ),
+                );
+                if !stats.secret_findings.is_empty() {
+                    print_colored(
+                        "red",
+                        "  🔐",
+                        &format!("发现疑似密钥 {} 处，已脱敏", stats.secret_findings.len()),
+                    );
+                }
+                if let Err(e) = check_secrets(config, &stats) {
+                    failure = Some(e);
+                    return false;
+                }
+            }
+            Err(e) => print_colored("red", "✗", &format!("[{}] 重新生成失败: {}", time, e)),
+        }
+        true
+    })?;
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
 // 文件收集
 // ═══════════════════════════════════════════════════════════════════════════
 
+/// 把 `-Entry` 参数规范为相对于目标目录的路径，支持相对当前目录或目标目录书写
+fn resolve_entries(config: &Config) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let base = fs::canonicalize(&config.path)?;
+    config
+        .entries
+        .iter()
+        .map(|entry| {
+            let candidates = [config.path.join(entry), PathBuf::from(entry)];
+            let absolute = candidates
+                .iter()
+                .filter(|p| p.is_file())
+                .find_map(|p| fs::canonicalize(p).ok())
+                .ok_or_else(|| format!("入口文件不存在: {}", entry))?;
+            let relative = absolute
+                .strip_prefix(&base)
+                .map_err(|_| format!("入口文件不在目标目录内: {}", entry))?;
+            Ok(relative.to_string_lossy().replace('\\', "/"))
+        })
+        .collect()
+}
+
 fn collect_files(
     dir: &Path,
-    base_path: &Path,
-    skip_set: &HashSet<String>,
-    ignore_dirs: &HashSet<String>,
-    ignore_files: &HashSet<String>,
-    include_exts: &Option<HashSet<String>>,
-    max_bytes: u64,
-    stats: &mut Stats,
-    entries: &mut Vec<FileEntry>,
-    tree: &mut Vec<String>,
+    config: &Config,
+    filters: &Filters,
+    scan: &mut ScanResult,
     depth: usize,
 ) -> io::Result<()> {
-    let dir_name = dir
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| ".".to_string());
+    let base_path = config.path.as_path();
+
+    let dir_name = if depth == 0 && config.reproducible {
+        source_name(dir)
+    } else {
+        dir.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string())
+    };
 
-    // 跳过忽略的目录（默认列表 + 用户自定义列表）
-    if IGNORED_DIRS.contains(&dir_name.as_str()) || ignore_dirs.contains(&dir_name) {
+    // 跳过忽略的目录（默认列表 + 用户自定义列表）；目标目录本身总是扫描
+    if depth > 0
+        && (IGNORED_DIRS.contains(&dir_name.as_str()) || config.ignore_dirs.contains(&dir_name))
+    {
         return Ok(());
     }
 
-    stats.dirs_count += 1;
+    scan.stats.dirs_count += 1;
 
     // 添加到目录树
     let prefix = if depth == 0 {
@@ -615,10 +1823,19 @@ fn collect_files(
     } else {
         "│   ".repeat(depth - 1) + "├── "
     };
-    tree.push(format!("{}{}/", prefix, dir_name));
+    scan.tree.push(format!("{}{}/", prefix, dir_name));
+
+    let relative_dir = dir
+        .strip_prefix(base_path)
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    scan.attributes.load_dir(dir, &relative_dir);
 
     let mut items: Vec<_> = fs::read_dir(dir)?.filter_map(|e| e.ok()).collect();
-    items.sort_by_key(|e| (e.path().is_file(), e.file_name()));
+    items.sort_by_cached_key(|e| {
+        let name = e.file_name();
+        (!e.path().is_dir(), sort_key(&name.to_string_lossy()), name)
+    });
 
     for entry in items {
         let path = entry.path();
@@ -626,25 +1843,46 @@ fn collect_files(
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_default();
+        let relative_path = path
+            .strip_prefix(base_path)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|_| path.to_string_lossy().to_string());
 
         if path.is_dir() {
-            collect_files(
-                &path,
-                base_path,
-                skip_set,
-                ignore_dirs,
-                ignore_files,
-                include_exts,
-                max_bytes,
-                stats,
-                entries,
-                tree,
-                depth + 1,
-            )?;
+            // export-ignore 的目录整体排除（与 git archive 一致）
+            if filters.allows_dir(&relative_path)
+                && !scan.attributes.lookup(&relative_path).export_ignore
+            {
+                collect_files(&path, config, filters, scan, depth + 1)?;
+            }
         } else {
-            // 检查特定文件名忽略
-            if ignore_files.contains(&file_name) {
-                stats.files_skipped_filter += 1;
+            // 输出文件位于扫描目录内时不能把自己收集进去
+            if filters.is_output(&path) {
+                continue;
+            }
+
+            // 检查特定文件名忽略和入口可达性
+            if config.ignore_files.contains(&file_name) || !filters.allows_file(&relative_path) {
+                scan.stats.files_skipped_filter += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::Filtered,
+                    config.max_bytes,
+                ));
+                continue;
+            }
+
+            // .gitattributes 中 export-ignore 的文件不属于发布内容，不收集也不列出
+            let attributes = scan.attributes.lookup(&relative_path);
+            if attributes.export_ignore {
+                scan.stats.files_skipped_filter += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::ExportIgnore,
+                    config.max_bytes,
+                ));
                 continue;
             }
 
@@ -654,18 +1892,86 @@ fn collect_files(
                 .map(|e| e.to_lowercase())
                 .unwrap_or_default();
 
+            // 敏感文件默认不收集，只在目录树中标记
+            if is_sensitive(&path, &file_name, &relative_path) {
+                let allowed = config
+                    .unsafe_include_sensitive
+                    .iter()
+                    .any(|p| path_matches(p, &file_name, &relative_path));
+                if !allowed {
+                    scan.stats.files_withheld += 1;
+                    scan.stats.skipped.push(Skipped::new(
+                        &path,
+                        relative_path,
+                        SkipReason::Sensitive,
+                        config.max_bytes,
+                    ));
+                    scan.tree.push(format!(
+                        "{}│   ├── {} (withheld)",
+                        "│   ".repeat(depth),
+                        file_name
+                    ));
+                    continue;
+                }
+                scan.stats.sensitive_included.push(relative_path.clone());
+            }
+
             // 检查白名单
-            if let Some(include) = include_exts
+            if let Some(include) = &config.include_exts
                 && !include.contains(&ext)
             {
-                stats.files_skipped_filter += 1;
+                scan.stats.files_skipped_filter += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::Filtered,
+                    config.max_bytes,
+                ));
+                continue;
+            }
+
+            // linguist-generated / linguist-vendored 的文件只在目录树中标记
+            if attributes.generated == Some(true) && !config.include_generated {
+                scan.stats.files_skipped_generated += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::Generated,
+                    config.max_bytes,
+                ));
+                scan.tree.push(format!(
+                    "{}│   ├── {} (generated)",
+                    "│   ".repeat(depth),
+                    file_name
+                ));
+                continue;
+            }
+            if attributes.vendored == Some(true) {
+                scan.stats.files_skipped_vendored += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::Vendored,
+                    config.max_bytes,
+                ));
+                scan.tree.push(format!(
+                    "{}│   ├── {} (vendored)",
+                    "│   ".repeat(depth),
+                    file_name
+                ));
                 continue;
             }
 
             // 检查黑名单
-            if skip_set.contains(&ext) {
-                stats.files_skipped_binary += 1;
-                tree.push(format!(
+            if filters.skip_exts.contains(&ext) {
+                scan.stats.files_skipped_binary += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::Binary,
+                    config.max_bytes,
+                ));
+                scan.tree.push(format!(
                     "{}│   ├── {} (binary)",
                     "│   ".repeat(depth),
                     file_name
@@ -675,9 +1981,16 @@ fn collect_files(
 
             // 检查文件大小
             let metadata = fs::metadata(&path)?;
-            if metadata.len() > max_bytes {
-                stats.files_skipped_size += 1;
-                tree.push(format!(
+            let too_large = metadata.len() > config.max_bytes;
+            if too_large && config.truncate.is_none() {
+                scan.stats.files_skipped_size += 1;
+                scan.stats.skipped.push(Skipped::new(
+                    &path,
+                    relative_path,
+                    SkipReason::TooLarge,
+                    config.max_bytes,
+                ));
+                scan.tree.push(format!(
                     "{}│   ├── {} ({})",
                     "│   ".repeat(depth),
                     file_name,
@@ -686,33 +1999,128 @@ fn collect_files(
                 continue;
             }
 
-            // 尝试读取文件
-            match fs::read_to_string(&path) {
-                Ok(content) => {
-                    let line_count = content.lines().count();
-                    let relative_path = path
-                        .strip_prefix(base_path)
-                        .map(|p| p.to_string_lossy().replace('\\', "/"))
-                        .unwrap_or_else(|_| path.to_string_lossy().to_string());
-
-                    print_progress(entries.len() + 1, &relative_path);
-
-                    entries.push(FileEntry {
+            // 统计文件（未变化的文件直接使用缓存结果）
+            let language = attributes.language.as_deref().map(lang::from_name);
+            match scan
+                .cache
+                .file_info(&path, &relative_path, &metadata, language.as_deref())
+            {
+                // 内容看起来是生成或压缩的；.gitattributes 明确声明不是生成文件时不判断
+                Ok(info)
+                    if !config.include_generated
+                        && attributes.generated.is_none()
+                        && let Some(kind) = info.generated =>
+                {
+                    let reason = match kind {
+                        generated::Kind::Generated => {
+                            scan.stats.files_skipped_generated += 1;
+                            SkipReason::Generated
+                        }
+                        generated::Kind::Minified => {
+                            scan.stats.files_skipped_minified += 1;
+                            SkipReason::Minified
+                        }
+                    };
+                    scan.stats.skipped.push(Skipped::new(
+                        &path,
+                        relative_path,
+                        reason,
+                        config.max_bytes,
+                    ));
+                    scan.tree.push(format!(
+                        "{}│   ├── {} ({})",
+                        "│   ".repeat(depth),
+                        file_name,
+                        kind.as_str()
+                    ));
+                }
+                Ok(info) => {
+                    let lines = info.lines;
+
+                    // 超过大小上限的文件按 -Truncate 截断，token 按保留的部分估算；
+                    // 截断后仍超过上限（行数太少或行太长）时与不截断时一样跳过
+                    let mut tokens = info.tokens;
+                    let mut omitted = 0;
+                    if too_large {
+                        let excerpt = config.truncate.and_then(|(head, tail)| {
+                            let content = scan.cache.read(&path, &relative_path).ok()?;
+                            truncate::fit(content, head, tail, config.max_bytes)
+                        });
+                        let Some(excerpt) = excerpt else {
+                            scan.stats.files_skipped_size += 1;
+                            scan.stats.skipped.push(Skipped::new(
+                                &path,
+                                relative_path,
+                                SkipReason::TooLarge,
+                                config.max_bytes,
+                            ));
+                            scan.tree.push(format!(
+                                "{}│   ├── {} ({})",
+                                "│   ".repeat(depth),
+                                file_name,
+                                format_size(metadata.len())
+                            ));
+                            continue;
+                        };
+                        tokens = estimate_tokens(&excerpt.text);
+                        omitted = excerpt.omitted;
+                    }
+
+                    print_progress(scan.entries.len() + 1, &relative_path);
+
+                    scan.entries.push(FileEntry {
                         relative_path,
                         extension: ext.clone(),
+                        language: info.language.clone(),
                         size: metadata.len(),
-                        line_count,
+                        line_count: info.line_count,
+                        lines,
+                        tokens,
+                        hash: info.hash,
+                        encoding: info.encoding,
+                        truncated: omitted > 0,
+                        modified: metadata.modified().ok(),
+                        score: None,
+                        tree_index: scan.tree.len(),
                     });
 
-                    stats.files_processed += 1;
-                    stats.total_bytes += metadata.len();
-                    stats.total_lines += line_count;
-                    *stats.file_types.entry(ext).or_insert(0) += 1;
-
-                    tree.push(format!("{}│   ├── {}", "│   ".repeat(depth), file_name));
+                    scan.stats.files_processed += 1;
+                    scan.stats.total_bytes += metadata.len();
+                    scan.stats.total_lines += info.line_count;
+                    scan.stats.total_tokens += tokens;
+                    if info.encoding.is_some() {
+                        scan.stats.files_transcoded += 1;
+                    }
+                    scan.stats.lines += lines;
+                    let language_stats = scan
+                        .stats
+                        .languages
+                        .entry(info.language.clone())
+                        .or_default();
+                    language_stats.files += 1;
+                    language_stats.lines += lines;
+                    *scan.stats.file_types.entry(ext).or_insert(0) += 1;
+
+                    if omitted > 0 {
+                        scan.tree.push(format!(
+                            "{}│   ├── {} ({}, truncated)",
+                            "│   ".repeat(depth),
+                            file_name,
+                            format_size(metadata.len())
+                        ));
+                    } else {
+                        scan.tree
+                            .push(format!("{}│   ├── {}", "│   ".repeat(depth), file_name));
+                    }
                 }
                 Err(_) => {
-                    stats.files_skipped_encoding += 1;
+                    scan.stats.files_skipped_encoding += 1;
+                    scan.stats.skipped.push(Skipped::new(
+                        &path,
+                        relative_path,
+                        SkipReason::Encoding,
+                        config.max_bytes,
+                    ));
                 }
             }
         }
@@ -725,165 +2133,341 @@ fn collect_files(
 // Markdown 输出
 // ═══════════════════════════════════════════════════════════════════════════
 
-fn write_header(
-    writer: &mut BufWriter<File>,
-    config: &Config,
-    _stats: &Stats,
-    entries: &[FileEntry],
-) -> io::Result<()> {
-    let now = Local::now();
-    let project_name = config
-        .path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| "Project".to_string());
-
-    writeln!(writer, "# 📦 {} - Code Collection", project_name)?;
-    writeln!(writer)?;
-    writeln!(writer, "> 由 Code Collector v{} 自动生成", VERSION)?;
-    writeln!(writer)?;
-    writeln!(writer, "| 属性 | 值 |")?;
-    writeln!(writer, "|------|-----|")?;
-    writeln!(
-        writer,
-        "| 📅 生成时间 | {} |",
-        now.format("%Y-%m-%d %H:%M:%S")
-    )?;
-    writeln!(writer, "| 📁 源目录 | `{}` |", config.path.display())?;
-    writeln!(writer, "| 📄 文件数量 | {} |", entries.len())?;
-    writeln!(writer)?;
-    writeln!(writer, "---")?;
-    writeln!(writer)?;
+/// `-Query` 时按查询给每个文件打分
+fn score_files(config: &Config, cache: &ContentCache, entries: &mut [FileEntry]) {
+    let Some(query) = &config.query else {
+        return;
+    };
+    let contents: Vec<String> = entries
+        .iter()
+        .map(|e| cache.decoded(&config.path, &e.relative_path))
+        .collect();
+    let edges = order::import_edges(entries, &contents, &config.path);
+    let scores = rank::score(query, entries, &contents, &edges);
+    for (entry, score) in entries.iter_mut().zip(scores) {
+        entry.score = Some(score);
+    }
+}
 
-    Ok(())
+/// 预算中文件内容以外的开销：`fixed` 为提示词以及模板和清单中与文件无关的部分，
+/// `per_file` 为每个写入的文件的标题、元信息行、围栏、目录行和清单记录，
+/// `per_excluded` 为每个超出预算的文件在表格、清单和目录树中占用的部分（均取平均）
+struct Overhead {
+    fixed: usize,
+    per_file: usize,
+    per_excluded: usize,
 }
 
-fn write_tree(writer: &mut BufWriter<File>, tree: &[String]) -> io::Result<()> {
-    writeln!(writer, "## 📂 目录结构")?;
-    writeln!(writer)?;
-    writeln!(writer, "```")?;
-    for line in tree {
-        writeln!(writer, "{}", line)?;
+impl Overhead {
+    /// 分别渲染不含文件、只含文件骨架（内容为空）、一个和全部文件超出预算的报告与清单，按差值估算
+    ///
+    /// `render` 返回给定统计、目录树和文件下报告与清单的 token 数
+    fn measure(
+        render: impl Fn(&Stats, &[String], &[RenderedFile], &[FileEntry]) -> Result<usize, String>,
+        stats: &Stats,
+        tree: &[String],
+        entries: &[FileEntry],
+        prepared: &[Prepared],
+    ) -> Result<Self, String> {
+        let skeletons: Vec<RenderedFile> = entries
+            .iter()
+            .zip(prepared)
+            .map(|(entry, file)| file.skeleton(entry))
+            .collect();
+        let mut written = stats.clone();
+        let mut excluded = (stats.clone(), tree.to_vec());
+        let mut first_excluded = (stats.clone(), tree.to_vec());
+        for (i, (entry, file)) in entries.iter().zip(prepared).enumerate() {
+            record(&mut written, entry, file);
+            exclude(&mut excluded.0, &mut excluded.1, entry, file);
+            if i == 0 {
+                exclude(&mut first_excluded.0, &mut first_excluded.1, entry, file);
+            }
+        }
+
+        let empty = render(stats, tree, &[], &[])?;
+        let full = render(&written, tree, &skeletons, entries)?;
+        let first = render(&first_excluded.0, &first_excluded.1, &[], &[])?;
+        let all = render(&excluded.0, &excluded.1, &[], &[])?;
+        let count = entries.len().max(1);
+        let per_excluded = match count {
+            1 => first.saturating_sub(empty),
+            _ => all.saturating_sub(first).div_ceil(count - 1),
+        };
+        // 超出预算表格的标题等只出现一次，计入固定开销
+        let heading = first.saturating_sub(empty).saturating_sub(per_excluded);
+        Ok(Self {
+            fixed: stats.prompt_tokens + empty + heading,
+            per_file: full.saturating_sub(empty).div_ceil(count),
+            per_excluded,
+        })
     }
-    writeln!(writer, "```")?;
-    writeln!(writer)?;
-    writeln!(writer, "---")?;
-    writeln!(writer)?;
+}
 
-    Ok(())
+/// 把文件记为超出预算：撤销统计，写入跳过列表和超出预算列表，并在目录树中标记
+fn exclude(stats: &mut Stats, tree: &mut [String], entry: &FileEntry, file: &Prepared) {
+    stats.forget(entry);
+    stats.skipped.push(Skipped {
+        path: entry.relative_path.clone(),
+        reason: SkipReason::Budget,
+        size: Some(entry.size),
+        sha256: Some(entry.hash.clone()),
+    });
+    stats.excluded.push((
+        entry.relative_path.clone(),
+        estimate_tokens(&file.content),
+        entry.score,
+    ));
+    tree[entry.tree_index].push_str(" (over budget)");
 }
 
-fn write_toc(writer: &mut BufWriter<File>, entries: &[FileEntry]) -> io::Result<()> {
-    writeln!(writer, "## 📑 文件目录")?;
-    writeln!(writer)?;
-    writeln!(writer, "| # | 文件 | 类型 | 行数 | 大小 |")?;
-    writeln!(writer, "|---|------|------|------|------|")?;
+/// `-MaxTokens` 时按分数（无查询时按当前顺序）装入预算，
+/// 放不下的文件从条目、处理结果和统计中移除，并在目录树中标记
+///
+/// 每个文件按处理后（大纲、精简、脱敏、行号等）内容的 token 数加上 `overhead.per_file` 计算
+fn select_files(
+    config: &Config,
+    overhead: Overhead,
+    entries: &mut Vec<FileEntry>,
+    prepared: &mut Vec<Prepared>,
+    stats: &mut Stats,
+    tree: &mut [String],
+) {
+    let Some(max_tokens) = config.max_tokens else {
+        return;
+    };
+    // 排序是稳定的：没有分数时保持 -Order 的顺序
+    let mut priority: Vec<usize> = (0..entries.len()).collect();
+    priority.sort_by(|&a, &b| {
+        let score = |i: usize| entries[i].score.unwrap_or(0.0);
+        score(b).total_cmp(&score(a))
+    });
+
+    // 超出预算的文件也占用少量 token：先按全部超出预留，装入一个文件再退回它的预留
+    let costs: Vec<usize> = prepared
+        .iter()
+        .map(|file| {
+            (estimate_tokens(&file.content) + overhead.per_file)
+                .saturating_sub(overhead.per_excluded)
+        })
+        .collect();
+    let reserved = overhead.fixed + overhead.per_excluded * entries.len();
+    let excluded = rank::pack(&costs, &priority, max_tokens.saturating_sub(reserved));
+
+    for &i in &excluded {
+        exclude(stats, tree, &entries[i], &prepared[i]);
+    }
+    let excluded: HashSet<usize> = excluded.into_iter().collect();
+    let keep = |i: usize| !excluded.contains(&i);
+    let mut index = 0;
+    entries.retain(|_| {
+        index += 1;
+        keep(index - 1)
+    });
+    let mut index = 0;
+    prepared.retain(|_| {
+        index += 1;
+        keep(index - 1)
+    });
+}
 
-    for (i, entry) in entries.iter().enumerate() {
-        let anchor = entry
-            .relative_path
-            .replace(['/', '.', ' '], "-")
-            .to_lowercase();
-        writeln!(
-            writer,
-            "| {} | [{}](#{}) | {} | {} | {} |",
-            i + 1,
-            entry.relative_path,
-            anchor,
-            entry.extension,
-            entry.line_count,
-            format_size(entry.size)
-        )?;
-    }
-
-    writeln!(writer)?;
-    writeln!(writer, "---")?;
-    writeln!(writer)?;
+/// 处理后的文件内容；文件最终写入报告时才计入统计
+struct Prepared {
+    content: String,
+    line_endings: Option<LineEndings>,
+    normalized: bool,
+    omitted: usize,
+    outlined: bool,
+    trimmed: bool,
+    findings: Vec<secrets::Finding>,
+    bytes_saved: u64,
+    tokens_saved: usize,
+}
 
-    Ok(())
+impl Prepared {
+    /// 内容为空的渲染条目，用于估算模板开销
+    fn skeleton<'a>(&self, entry: &'a FileEntry) -> RenderedFile<'a> {
+        RenderedFile {
+            entry,
+            content: String::new(),
+            line_endings: self.line_endings,
+            normalized: self.normalized,
+            omitted: self.omitted,
+            outlined: self.outlined,
+            trimmed: self.trimmed,
+            redactions: self.findings.len(),
+        }
+    }
 }
 
-fn write_files(
-    writer: &mut BufWriter<File>,
-    base_path: &Path,
+/// 按规范化、截断、大纲、精简、脱敏和行号选项处理每个文件的内容
+fn prepare_files(
+    config: &Config,
     entries: &[FileEntry],
-) -> io::Result<()> {
-    writeln!(writer, "## 📄 文件内容")?;
-    writeln!(writer)?;
-
+    cache: &ContentCache,
+) -> io::Result<Vec<Prepared>> {
+    let mut files = Vec::with_capacity(entries.len());
     for (i, entry) in entries.iter().enumerate() {
         print_progress(i + 1, &entry.relative_path);
+        let content = match cache.get(&entry.relative_path) {
+            Some(content) => content.to_string(),
+            None => {
+                encoding::read(&config.path.join(&entry.relative_path), &config.encodings)?.text
+            }
+        };
+        files.push(prepare(config, entry, content));
+    }
+    clear_progress();
+    Ok(files)
+}
 
-        let file_path = base_path.join(&entry.relative_path);
-        let content = fs::read_to_string(&file_path)?;
-        let lang = get_language(&entry.extension);
+fn prepare(config: &Config, entry: &FileEntry, mut content: String) -> Prepared {
+    let lang = entry.language.as_str();
+    // 密钥的行号按原文件报告
+    let original = config.scan_secrets.then(|| content.clone());
+
+    // 统一换行、制表符和 BOM；换行方式按原内容记录
+    let line_endings = normalize::line_endings(&content);
+    let mut normalized = false;
+    if config.normalize.is_enabled() {
+        let normal = normalize::apply(&content, &config.normalize);
+        normalized = normal != content;
+        if normalized {
+            content = normal;
+        }
+    }
 
-        // 动态计算需要的反引号数量，确保比内容中最长的反引号序列多
-        let max_backticks = count_max_backticks(&content);
-        let fence_count = if max_backticks >= 3 {
-            max_backticks + 1
-        } else {
-            3
-        };
-        let fence: String = "`".repeat(fence_count);
-
-        writeln!(writer, "### {}", entry.relative_path)?;
-        writeln!(writer)?;
-        writeln!(
-            writer,
-            "> 📏 {} 行 | 💾 {}",
-            entry.line_count,
-            format_size(entry.size)
-        )?;
-        writeln!(writer)?;
-        writeln!(writer, "{}{}", fence, lang)?;
-        write!(writer, "{}", content)?;
-        if !content.ends_with('\n') {
-            writeln!(writer)?;
+    // 超过大小上限的文件只保留开头和结尾，不再生成大纲
+    let mut omitted = 0;
+    if entry.truncated
+        && let Some((head, tail)) = config.truncate
+        && let Some(excerpt) = truncate::excerpt(&content, head, tail)
+    {
+        content = excerpt.text;
+        omitted = excerpt.omitted;
+    }
+
+    // 大纲模式：用声明骨架替换全文
+    let mut outlined = false;
+    if config.outline
+        && omitted == 0
+        && !keeps_full_content(config, &entry.relative_path)
+        && let Some(skeleton) = outline::outline(&content, lang)
+    {
+        content = skeleton;
+        outlined = true;
+    }
+
+    // 按选项精简内容，并记录节省的体积
+    let mut trimmed = false;
+    let mut bytes_saved = 0;
+    let mut tokens_saved = 0;
+    if config.transform.is_enabled() {
+        let transformed = transform::apply(&content, lang, &config.transform);
+        bytes_saved = (content.len() - transformed.len().min(content.len())) as u64;
+        tokens_saved = estimate_tokens(&content).saturating_sub(estimate_tokens(&transformed));
+        trimmed = transformed != content;
+        content = transformed;
+    }
+
+    // 扫描密钥并原地脱敏
+    let mut findings = Vec::new();
+    if let Some(original) = &original {
+        let (safe, found) = secrets::redact(&content, original);
+        if !found.is_empty() {
+            content = safe;
+            findings = found;
         }
-        writeln!(writer, "{}", fence)?;
-        writeln!(writer)?;
     }
 
-    println!(); // 清除进度行
-    Ok(())
+    // 行号在最后加上，编号对应写入的内容；unpack/apply 解析时会去掉
+    if config.line_numbers {
+        // 省略标记之后的行按原文件的行号编号
+        let gap = content
+            .lines()
+            .position(|line| omitted > 0 && line == truncate::marker(omitted))
+            .map(|at| (at, omitted));
+        content = bundle::number_lines(&content, gap);
+    }
+
+    Prepared {
+        content,
+        line_endings,
+        normalized,
+        omitted,
+        outlined,
+        trimmed,
+        findings,
+        bytes_saved,
+        tokens_saved,
+    }
 }
 
-fn write_stats(writer: &mut BufWriter<File>, stats: &Stats) -> io::Result<()> {
-    writeln!(writer, "---")?;
-    writeln!(writer)?;
-    writeln!(writer, "## 📊 统计信息")?;
-    writeln!(writer)?;
-    writeln!(writer, "### 文件类型分布")?;
-    writeln!(writer)?;
-    writeln!(writer, "| 扩展名 | 文件数 |")?;
-    writeln!(writer, "|--------|--------|")?;
-
-    let mut types: Vec<_> = stats.file_types.iter().collect();
-    types.sort_by(|a, b| b.1.cmp(a.1));
-
-    for (ext, count) in types {
-        writeln!(writer, "| .{} | {} |", ext, count)?;
-    }
-
-    writeln!(writer)?;
-    writeln!(writer, "### 汇总")?;
-    writeln!(writer)?;
-    writeln!(writer, "- **处理文件**: {}", stats.files_processed)?;
-    writeln!(writer, "- **总代码行数**: {}", stats.total_lines)?;
-    writeln!(writer, "- **总大小**: {}", format_size(stats.total_bytes))?;
-    writeln!(writer, "- **跳过（过大）**: {}", stats.files_skipped_size)?;
-    writeln!(
-        writer,
-        "- **跳过（二进制）**: {}",
-        stats.files_skipped_binary
-    )?;
-    writeln!(
-        writer,
-        "- **跳过（编码问题）**: {}",
-        stats.files_skipped_encoding
-    )?;
-    writeln!(writer)?;
+/// 把写入报告的文件计入统计，记录改写情况
+fn record(stats: &mut Stats, entry: &FileEntry, file: &Prepared) {
+    let path = &entry.relative_path;
+    let mut altered = Vec::new();
+    if file.normalized {
+        stats.files_normalized += 1;
+        altered.push("normalized");
+    }
+    if file.omitted > 0 {
+        stats.truncated.push((path.clone(), file.omitted));
+        altered.push("truncated");
+    }
+    if file.outlined {
+        stats.files_outlined += 1;
+        altered.push("outline");
+    }
+    if file.trimmed {
+        altered.push("trimmed");
+    }
+    if !file.findings.is_empty() {
+        altered.push("redacted");
+    }
+    if !altered.is_empty() {
+        stats.altered.insert(path.clone(), altered);
+    }
+    stats.bytes_saved += file.bytes_saved;
+    stats.tokens_saved += file.tokens_saved;
+    stats
+        .secret_findings
+        .extend(file.findings.iter().map(|f| (path.clone(), f.clone())));
+}
 
-    Ok(())
+/// 把最终写入报告的文件计入统计，转为渲染条目
+fn render_files<'a>(
+    entries: &'a [FileEntry],
+    prepared: Vec<Prepared>,
+    stats: &mut Stats,
+) -> Vec<RenderedFile<'a>> {
+    entries
+        .iter()
+        .zip(prepared)
+        .map(|(entry, file)| {
+            record(stats, entry, &file);
+            RenderedFile {
+                entry,
+                redactions: file.findings.len(),
+                content: file.content,
+                line_endings: file.line_endings,
+                normalized: file.normalized,
+                omitted: file.omitted,
+                outlined: file.outlined,
+                trimmed: file.trimmed,
+            }
+        })
+        .collect()
+}
+
+/// 大纲模式下是否仍输出全文（按相对路径、文件名或所在目录匹配）
+fn keeps_full_content(config: &Config, relative_path: &str) -> bool {
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    config.outline_keep.iter().any(|keep| {
+        relative_path == keep
+            || file_name == keep
+            || relative_path
+                .strip_prefix(keep.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
 }
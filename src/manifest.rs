@@ -0,0 +1,188 @@
+use crate::{Config, FileEntry, IGNORED_DIRS, Stats, VERSION, prompt, source_display};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::path::Path;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 机器可读清单
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 清单以 HTML 注释嵌入 Markdown，渲染时不可见
+const OPEN: &str = "<!-- code-collector-manifest";
+const CLOSE: &str = "-->";
+
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub tool: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    pub source: String,
+    /// 生效的配置（命令行参数与默认值合并后）
+    pub config: Value,
+    pub files: Vec<IncludedFile>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IncludedFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub lines: usize,
+    pub tokens: usize,
+    /// 原始编码；UTF-8 时省略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// 写入内容相对原文件的改写：normalized、truncated、outline、trimmed、redacted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub altered: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub reason: String,
+    /// 与查询的相关度（超出 token 预算且指定了 `-Query` 时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl Manifest {
+    pub fn build(
+        config: &Config,
+        generated: Option<DateTime<FixedOffset>>,
+        stats: &Stats,
+        entries: &[FileEntry],
+    ) -> Self {
+        let files = entries
+            .iter()
+            .map(|entry| IncludedFile {
+                path: entry.relative_path.clone(),
+                size: entry.size,
+                sha256: entry.hash.clone(),
+                lines: entry.line_count,
+                tokens: entry.tokens,
+                encoding: entry.encoding.map(str::to_string),
+                altered: stats
+                    .altered
+                    .get(&entry.relative_path)
+                    .map(|marks| marks.iter().map(|m| m.to_string()).collect())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        // 大小和哈希在扫描时已记录
+        let skipped = stats
+            .skipped
+            .iter()
+            .map(|skipped| SkippedFile {
+                path: skipped.path.clone(),
+                size: skipped.size,
+                sha256: skipped.sha256.clone(),
+                reason: skipped.reason.as_str().to_string(),
+                score: stats
+                    .excluded
+                    .iter()
+                    .find(|(excluded, _, _)| *excluded == skipped.path)
+                    .and_then(|(_, _, score)| *score),
+            })
+            .collect();
+
+        Self {
+            tool: "code-collector".to_string(),
+            version: VERSION.to_string(),
+            generated: generated.map(|time| time.to_rfc3339()),
+            source: source_display(config),
+            config: config_json(config),
+            files,
+            skipped,
+        }
+    }
+
+    /// 渲染为 HTML 注释；JSON 字符串中的 `-->` 转义，避免提前结束注释
+    pub fn render(&self) -> String {
+        let body = serde_json::to_string_pretty(self).unwrap_or_default();
+        format!(
+            "{}\n{}\n{}\n",
+            OPEN,
+            body.replace("-->", "--\\u003e"),
+            CLOSE
+        )
+    }
+}
+
+/// 从 Markdown 中取出清单；没有清单或格式不对时返回 `None`
+pub fn parse(text: &str) -> Option<Manifest> {
+    let start = text.rfind(OPEN)? + OPEN.len();
+    let end = start + text[start..].find(CLOSE)?;
+    serde_json::from_str(&text[start..end]).ok()
+}
+
+pub fn config_json(config: &Config) -> Value {
+    json!({
+        "max_bytes": config.max_bytes,
+        "truncate": config.truncate,
+        "skip_exts": sorted(&config.skip_exts),
+        "include_exts": config.include_exts.as_ref().map(sorted),
+        "ignore_dirs": sorted(&config.ignore_dirs),
+        "builtin_ignore_dirs": IGNORED_DIRS,
+        "ignore_files": sorted(&config.ignore_files),
+        "tree": config.show_tree,
+        "toc": config.show_toc,
+        "strip_comments": config.transform.strip_comments,
+        "keep_doc_comments": config.transform.keep_doc_comments,
+        "collapse_blank_lines": config.transform.collapse_blank_lines,
+        "trim_trailing": config.transform.trim_trailing,
+        "strip_license": config.transform.strip_license,
+        "normalize_line_endings": config.normalize.line_endings,
+        "expand_tabs": config.normalize.tab_width,
+        "strip_bom": config.normalize.strip_bom,
+        "outline": config.outline,
+        "line_numbers": config.line_numbers,
+        "outline_keep": config.outline_keep,
+        "entries": config.entries,
+        "entry_depth": config.entry_depth,
+        "graph": config.graph.map(|g| g.name()),
+        "graph_collapse": config.graph_collapse,
+        "order": config.order.name(),
+        "priority": config.priority,
+        "query": config.query,
+        "max_tokens": config.max_tokens,
+        "scan_secrets": config.scan_secrets,
+        "include_generated": config.include_generated,
+        "encodings": config.encodings.iter().map(|e| e.name()).collect::<Vec<_>>(),
+        "unsafe_include_sensitive": config.unsafe_include_sensitive,
+        "reproducible": config.reproducible,
+        "template": config.template.as_deref().map(|path| template_name(config, path)),
+        "prompt_prefix": prompt_source(config.prompt_prefix.as_deref()),
+        "prompt_suffix": prompt_source(config.prompt_suffix.as_deref()),
+    })
+}
+
+/// 可复现模式下只记录模板文件名，避免写入本机路径
+fn template_name(config: &Config, path: &Path) -> String {
+    match path.file_name() {
+        Some(name) if config.reproducible => name.to_string_lossy().to_string(),
+        _ => path.display().to_string(),
+    }
+}
+
+/// 提示词只记录来源（片段名、文件名或 `text`），不记录全文
+fn prompt_source(value: Option<&str>) -> Option<String> {
+    value
+        .and_then(|v| prompt::resolve(v).ok())
+        .map(|p| p.source)
+}
+
+fn sorted(set: &HashSet<String>) -> Vec<&String> {
+    let mut items: Vec<&String> = set.iter().collect();
+    items.sort();
+    items
+}
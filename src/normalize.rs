@@ -0,0 +1,92 @@
+// ═══════════════════════════════════════════════════════════════════════════
+// 换行与空白规范化
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 写入前统一换行符、制表符和 BOM，减少模型看到的无关差异
+#[derive(Clone, Default)]
+pub struct NormalizeOptions {
+    pub line_endings: bool,
+    pub tab_width: Option<usize>,
+    pub strip_bom: bool,
+}
+
+impl NormalizeOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.line_endings || self.tab_width.is_some() || self.strip_bom
+    }
+}
+
+/// 原文件中不是纯 LF 的换行方式，写入元信息行供审阅者参考
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineEndings {
+    Crlf,
+    /// 只有 `\r`（经典 Mac OS）
+    Cr,
+    /// 同一文件中混用多种换行
+    Mixed,
+}
+
+impl LineEndings {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Crlf => "crlf",
+            Self::Cr => "cr",
+            Self::Mixed => "mixed",
+        }
+    }
+}
+
+/// 识别换行方式；纯 LF 或没有换行时返回 `None`
+pub fn line_endings(content: &str) -> Option<LineEndings> {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    let cr = content.matches('\r').count() - crlf;
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, _, false) => None,
+        (true, false, false) => Some(LineEndings::Crlf),
+        (false, false, true) => Some(LineEndings::Cr),
+        _ => Some(LineEndings::Mixed),
+    }
+}
+
+/// 按选项规范化内容
+pub fn apply(content: &str, options: &NormalizeOptions) -> String {
+    let mut text = content;
+    if options.strip_bom {
+        text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    }
+
+    let mut output = if options.line_endings {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    } else {
+        text.to_string()
+    };
+    if let Some(width) = options.tab_width {
+        output = expand_tabs(&output, width);
+    }
+    output
+}
+
+/// 把制表符展开到下一个制表位（按字符计列）
+fn expand_tabs(content: &str, width: usize) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut column = 0;
+    for c in content.chars() {
+        match c {
+            '\t' => {
+                let spaces = width - column % width;
+                output.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' | '\r' => {
+                output.push(c);
+                column = 0;
+            }
+            _ => {
+                output.push(c);
+                column += 1;
+            }
+        }
+    }
+    output
+}
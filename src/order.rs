@@ -0,0 +1,178 @@
+use crate::{Config, ContentCache, FileEntry, imports, path_matches, sort_key};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 文件顺序：目录、优先级、大小、依赖、修改时间、名称
+// ═══════════════════════════════════════════════════════════════════════════
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Order {
+    /// 与目录树相同：目录在前，同层按名称
+    #[default]
+    Tree,
+    /// 按优先级模式：说明文档、项目清单和入口文件在前，测试在后
+    Priority,
+    /// 从小到大
+    Size,
+    /// 被依赖的文件在前
+    Dependency,
+    /// 最近修改的在前
+    Recent,
+    /// 按完整路径的字母顺序
+    Name,
+}
+
+impl Order {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "tree" => Some(Self::Tree),
+            "priority" => Some(Self::Priority),
+            "size" => Some(Self::Size),
+            "dependency" | "deps" => Some(Self::Dependency),
+            "recent" => Some(Self::Recent),
+            "name" | "alphabetical" => Some(Self::Name),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tree => "tree",
+            Self::Priority => "priority",
+            Self::Size => "size",
+            Self::Dependency => "dependency",
+            Self::Recent => "recent",
+            Self::Name => "name",
+        }
+    }
+}
+
+/// 未指定 `-Priority` 时的优先级模式（比较时忽略大小写）
+pub const DEFAULT_PRIORITY: &[&str] = &[
+    "readme*",
+    "cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "setup.cfg",
+    "requirements*.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle*",
+    "settings.gradle*",
+    "cmakelists.txt",
+    "makefile",
+    "dockerfile",
+    "main.*",
+    "lib.rs",
+    "index.*",
+    "app.*",
+    "__main__.py",
+];
+
+/// 测试文件，优先级排序时放在最后
+const TEST_PATTERNS: &[&str] = &[
+    "**/test/**",
+    "**/tests/**",
+    "**/__tests__/**",
+    "**/spec/**",
+    "test_*",
+    "*_test.*",
+    "*.test.*",
+    "*.spec.*",
+    "*_spec.*",
+];
+
+/// 按配置的顺序重排收集到的文件；排序是稳定的，相同位置的文件保持目录顺序
+pub fn sort(entries: &mut Vec<FileEntry>, config: &Config, cache: &ContentCache) {
+    match config.order {
+        Order::Tree => {}
+        Order::Priority => {
+            let patterns: Vec<String> = if config.priority.is_empty() {
+                DEFAULT_PRIORITY.iter().map(|p| p.to_string()).collect()
+            } else {
+                config.priority.iter().map(|p| p.to_lowercase()).collect()
+            };
+            entries.sort_by_cached_key(|e| priority_rank(&e.relative_path, &patterns));
+        }
+        Order::Size => entries.sort_by_key(|e| e.size),
+        Order::Recent => entries.sort_by_key(|e| Reverse(e.modified)),
+        Order::Name => entries.sort_by_cached_key(|e| sort_key(&e.relative_path)),
+        Order::Dependency => {
+            let contents: Vec<String> = entries
+                .iter()
+                .map(|e| cache.decoded(&config.path, &e.relative_path))
+                .collect();
+            let deps = import_edges(entries, &contents, &config.path);
+            dependency_order(entries, &deps);
+        }
+    }
+}
+
+/// 第一个匹配的模式的序号；都不匹配的排在中间，测试排在最后
+fn priority_rank(relative_path: &str, patterns: &[String]) -> usize {
+    let path = relative_path.to_lowercase();
+    let file_name = path.rsplit('/').next().unwrap_or(&path);
+    if TEST_PATTERNS
+        .iter()
+        .any(|p| path_matches(p, file_name, &path))
+    {
+        return patterns.len() + 1;
+    }
+    patterns
+        .iter()
+        .position(|p| path_matches(p, file_name, &path))
+        .unwrap_or(patterns.len())
+}
+
+/// 每个文件导入的其他已收集文件（`entries` 中的下标）；`contents` 为解码后的内容
+pub fn import_edges(entries: &[FileEntry], contents: &[String], base: &Path) -> Vec<Vec<usize>> {
+    let index: HashMap<&str, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.relative_path.as_str(), i))
+        .collect();
+    entries
+        .iter()
+        .zip(contents)
+        .map(|(e, content)| {
+            imports::local_imports(base, &e.relative_path, content, &e.language)
+                .iter()
+                .filter_map(|path| index.get(path.as_str()).copied())
+                .collect()
+        })
+        .collect()
+}
+
+/// 深度优先的后序遍历：文件排在它导入的文件之后；循环依赖按目录顺序断开
+fn dependency_order(entries: &mut Vec<FileEntry>, deps: &[Vec<usize>]) {
+    let mut visited = vec![false; entries.len()];
+    let mut order = Vec::with_capacity(entries.len());
+    for start in 0..entries.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some(top) = stack.last_mut() {
+            let (node, child) = *top;
+            top.1 += 1;
+            match deps[node].get(child) {
+                Some(&next) if !visited[next] => {
+                    visited[next] = true;
+                    stack.push((next, 0));
+                }
+                Some(_) => {}
+                None => {
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<FileEntry>> = entries.drain(..).map(Some).collect();
+    entries.extend(order.into_iter().filter_map(|i| slots[i].take()));
+}
@@ -0,0 +1,521 @@
+use tree_sitter::{Language, Node, Parser};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 大纲模式：用 tree-sitter 解析，只保留声明骨架
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 节点在大纲中的处理方式
+enum Action<'tree> {
+    /// 原样保留（导入、包声明等）
+    Keep,
+    /// 声明，保留签名并省略其中的函数体
+    Item(Node<'tree>),
+    /// 容器声明（impl、class 等），逐个处理成员；参数为成员所在的 body 节点
+    Container(Node<'tree>, Node<'tree>),
+    /// 删除
+    Drop,
+}
+
+/// 单门语言的大纲规则
+struct Grammar {
+    language: fn() -> Language,
+    /// 注释、属性等依附于后续声明的节点
+    attached: &'static [&'static str],
+    /// 函数体会被省略的节点
+    functions: &'static [&'static str],
+    classify: for<'t> fn(Node<'t>, bool) -> Action<'t>,
+    visible: fn(Node, &str, Option<Node>) -> bool,
+    python_blocks: bool,
+}
+
+fn grammar_for(lang: &str) -> Option<Grammar> {
+    let grammar = match lang {
+        "rust" => Grammar {
+            language: || tree_sitter_rust::LANGUAGE.into(),
+            attached: &["line_comment", "block_comment", "attribute_item"],
+            functions: &["function_item", "closure_expression"],
+            classify: classify_rust,
+            visible: visible_rust,
+            python_blocks: false,
+        },
+        "python" => Grammar {
+            language: || tree_sitter_python::LANGUAGE.into(),
+            attached: &["comment"],
+            functions: &["function_definition"],
+            classify: classify_python,
+            visible: visible_python,
+            python_blocks: true,
+        },
+        "typescript" | "tsx" => Grammar {
+            language: if lang == "tsx" {
+                || tree_sitter_typescript::LANGUAGE_TSX.into()
+            } else {
+                || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()
+            },
+            attached: &["comment", "decorator"],
+            functions: &[
+                "function_declaration",
+                "generator_function_declaration",
+                "function_expression",
+                "arrow_function",
+                "method_definition",
+            ],
+            classify: classify_typescript,
+            visible: visible_typescript,
+            python_blocks: false,
+        },
+        "go" => Grammar {
+            language: || tree_sitter_go::LANGUAGE.into(),
+            attached: &["comment"],
+            functions: &["function_declaration", "method_declaration", "func_literal"],
+            classify: classify_go,
+            visible: visible_go,
+            python_blocks: false,
+        },
+        "java" => Grammar {
+            language: || tree_sitter_java::LANGUAGE.into(),
+            attached: &["line_comment", "block_comment"],
+            functions: &[
+                "method_declaration",
+                "constructor_declaration",
+                "lambda_expression",
+            ],
+            classify: classify_java,
+            visible: visible_java,
+            python_blocks: false,
+        },
+        _ => return None,
+    };
+    Some(grammar)
+}
+
+/// 生成文件大纲；语言不支持或解析失败时返回 `None`
+///
+/// 默认只保留公开声明；若文件中没有任何公开声明（如二进制入口），则保留全部声明。
+pub fn outline(content: &str, lang: &str) -> Option<String> {
+    let grammar = grammar_for(lang)?;
+    let mut parser = Parser::new();
+    parser.set_language(&(grammar.language)()).ok()?;
+    let tree = parser.parse(content, None)?;
+    let root = tree.root_node();
+
+    let mut builder = Builder {
+        grammar: &grammar,
+        src: content,
+        edits: Vec::new(),
+        public_only: true,
+        declarations: 0,
+    };
+    builder.members(root, None, false);
+    if builder.declarations == 0 {
+        builder.edits.clear();
+        builder.public_only = false;
+        builder.members(root, None, false);
+    }
+
+    Some(builder.finish())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 编辑构建
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Builder<'a> {
+    grammar: &'a Grammar,
+    src: &'a str,
+    /// （开始, 结束, 替换文本）
+    edits: Vec<(usize, usize, String)>,
+    public_only: bool,
+    /// 顶层保留的声明数（不含 impl 块），用于判断是否需要回退到全部声明
+    declarations: usize,
+}
+
+impl Builder<'_> {
+    /// 处理容器内的成员，返回保留的声明数量
+    fn members(&mut self, body: Node, parent: Option<Node>, nested: bool) -> usize {
+        let mut kept = 0;
+        let mut pending: Vec<Node> = Vec::new();
+        let mut cursor = body.walk();
+        let children: Vec<Node> = body.named_children(&mut cursor).collect();
+
+        for child in children {
+            if self.grammar.attached.contains(&child.kind()) {
+                pending.push(child);
+                continue;
+            }
+
+            let action = (self.grammar.classify)(child, nested);
+            let declaration = !matches!(action, Action::Keep | Action::Drop);
+            let keep = match action {
+                Action::Keep => true,
+                Action::Item(item) => {
+                    let visible = self.is_visible(item, parent);
+                    if visible {
+                        self.elide_bodies(child);
+                        kept += 1;
+                    }
+                    visible
+                }
+                Action::Container(item, inner) => {
+                    let members = self.members(inner, Some(item), true);
+                    let visible = self.is_visible(item, parent) && (members > 0 || is_empty(inner));
+                    if visible {
+                        kept += 1;
+                    }
+                    visible
+                }
+                Action::Drop => false,
+            };
+
+            if keep && declaration && parent.is_none() && child.kind() != "impl_item" {
+                self.declarations += 1;
+            }
+            if !keep {
+                for node in pending.iter().chain(std::iter::once(&child)) {
+                    self.delete(*node);
+                }
+            }
+            pending.clear();
+        }
+
+        // 末尾孤立的注释，顶层保留（可能是文件说明），容器内删除
+        if nested {
+            for node in pending {
+                self.delete(node);
+            }
+        }
+
+        kept
+    }
+
+    fn is_visible(&self, item: Node, parent: Option<Node>) -> bool {
+        !self.public_only || (self.grammar.visible)(item, self.src, parent)
+    }
+
+    /// 省略节点内所有函数体
+    fn elide_bodies(&mut self, node: Node) {
+        if self.grammar.functions.contains(&node.kind())
+            && let Some(body) = node.child_by_field_name("body")
+            && is_block(body)
+        {
+            self.elide(body);
+            return;
+        }
+
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        for child in children {
+            self.elide_bodies(child);
+        }
+    }
+
+    fn elide(&mut self, body: Node) {
+        if !self.grammar.python_blocks {
+            self.edits
+                .push((body.start_byte(), body.end_byte(), "{ ... }".to_string()));
+            return;
+        }
+
+        // Python：保留文档字符串，其余替换为 `...`
+        let docstring = body
+            .named_child(0)
+            .filter(|n| n.kind() == "expression_statement" && is_docstring(*n));
+        match docstring {
+            Some(doc) if doc.end_byte() < body.end_byte() => {
+                let line_start = self.src[..body.start_byte()]
+                    .rfind('\n')
+                    .map_or(0, |p| p + 1);
+                let indent = &self.src[line_start..body.start_byte()];
+                self.edits
+                    .push((doc.end_byte(), body.end_byte(), format!("\n{}...", indent)));
+            }
+            Some(_) => {}
+            None => self
+                .edits
+                .push((body.start_byte(), body.end_byte(), "...".to_string())),
+        }
+    }
+
+    /// 删除节点；节点独占整行时连同缩进和换行一起删除
+    fn delete(&mut self, node: Node) {
+        let bytes = self.src.as_bytes();
+        let mut start = node.start_byte();
+        let mut end = node.end_byte();
+
+        let line_start = self.src[..start].rfind('\n').map_or(0, |p| p + 1);
+        if self.src[line_start..start].trim().is_empty() {
+            start = line_start;
+        }
+        // TypeScript 类字段等末尾的分号是独立的匿名节点，一并删除
+        if bytes.get(end) == Some(&b';') {
+            end += 1;
+        }
+        let line_end = self.src[end..]
+            .find('\n')
+            .map_or(bytes.len(), |p| end + p + 1);
+        if self.src[end..line_end].trim().is_empty() {
+            end = line_end;
+        }
+
+        self.edits.push((start, end, String::new()));
+    }
+
+    fn finish(mut self) -> String {
+        self.edits.sort_by_key(|(start, _, _)| *start);
+
+        let mut output = String::with_capacity(self.src.len() / 2);
+        let mut cursor = 0;
+        for (start, end, replacement) in &self.edits {
+            if *start < cursor {
+                continue;
+            }
+            output.push_str(&self.src[cursor..*start]);
+            output.push_str(replacement);
+            cursor = *end;
+        }
+        output.push_str(&self.src[cursor..]);
+
+        // 删除声明后可能留下大段空行，最多保留一行
+        let mut compact = String::with_capacity(output.len());
+        let mut blank_run = 0;
+        for line in output.split_inclusive('\n') {
+            if line.trim().is_empty() {
+                blank_run += 1;
+                if blank_run > 1 {
+                    continue;
+                }
+            } else {
+                blank_run = 0;
+            }
+            compact.push_str(line);
+        }
+        let trimmed = compact.trim_start_matches(['\r', '\n']).trim_end();
+        format!("{}\n", trimmed)
+    }
+}
+
+fn is_block(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "block" | "statement_block" | "constructor_body"
+    )
+}
+
+fn is_empty(node: Node) -> bool {
+    node.named_child_count() == 0
+}
+
+fn name_of<'s>(node: Node, src: &'s str) -> &'s str {
+    node.child_by_field_name("name")
+        .map_or("", |n| &src[n.byte_range()])
+}
+
+fn has_child_kind(node: Node, kind: &str) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).any(|c| c.kind() == kind)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Rust
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn classify_rust<'t>(node: Node<'t>, _nested: bool) -> Action<'t> {
+    match node.kind() {
+        "use_declaration" | "extern_crate_declaration" | "inner_attribute_item" => Action::Keep,
+        "impl_item" | "trait_item" | "mod_item" | "foreign_mod_item" => {
+            match node.child_by_field_name("body") {
+                Some(body) => Action::Container(node, body),
+                None => Action::Item(node),
+            }
+        }
+        "function_item"
+        | "function_signature_item"
+        | "struct_item"
+        | "enum_item"
+        | "union_item"
+        | "type_item"
+        | "const_item"
+        | "static_item"
+        | "macro_definition"
+        | "associated_type" => Action::Item(node),
+        _ => Action::Drop,
+    }
+}
+
+fn visible_rust(node: Node, _src: &str, parent: Option<Node>) -> bool {
+    match parent {
+        // trait 成员和 trait 实现的成员与 trait 本身可见性一致
+        Some(p) if p.kind() == "trait_item" => return true,
+        Some(p) if p.kind() == "impl_item" && p.child_by_field_name("trait").is_some() => {
+            return true;
+        }
+        _ => {}
+    }
+    node.kind() == "impl_item"
+        || node.kind() == "macro_definition"
+        || has_child_kind(node, "visibility_modifier")
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Python
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn classify_python<'t>(node: Node<'t>, nested: bool) -> Action<'t> {
+    let definition = if node.kind() == "decorated_definition" {
+        match node.child_by_field_name("definition") {
+            Some(def) => def,
+            None => return Action::Drop,
+        }
+    } else {
+        node
+    };
+
+    match definition.kind() {
+        "import_statement" | "import_from_statement" | "future_import_statement" => Action::Keep,
+        "class_definition" => match definition.child_by_field_name("body") {
+            Some(body) => Action::Container(definition, body),
+            None => Action::Item(definition),
+        },
+        "function_definition" => Action::Item(definition),
+        // 类属性声明和模块文档字符串保留，其余模块级语句删除
+        "expression_statement" if nested || is_docstring(definition) => Action::Keep,
+        _ => Action::Drop,
+    }
+}
+
+fn is_docstring(node: Node) -> bool {
+    node.prev_named_sibling().is_none() && node.named_child(0).is_some_and(|n| n.kind() == "string")
+}
+
+fn visible_python(node: Node, src: &str, _parent: Option<Node>) -> bool {
+    let name = name_of(node, src);
+    !name.starts_with('_') || (name.starts_with("__") && name.ends_with("__"))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// TypeScript
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn classify_typescript<'t>(node: Node<'t>, _nested: bool) -> Action<'t> {
+    let declaration = if node.kind() == "export_statement" {
+        match node.child_by_field_name("declaration") {
+            Some(decl) => decl,
+            // `export { a, b }`、`export * from` 等
+            None => return Action::Keep,
+        }
+    } else {
+        node
+    };
+
+    match declaration.kind() {
+        "import_statement" => Action::Keep,
+        "class_declaration" | "abstract_class_declaration" | "class" => {
+            match declaration.child_by_field_name("body") {
+                Some(body) => Action::Container(node, body),
+                None => Action::Item(node),
+            }
+        }
+        "function_declaration"
+        | "generator_function_declaration"
+        | "function_signature"
+        | "interface_declaration"
+        | "type_alias_declaration"
+        | "enum_declaration"
+        | "lexical_declaration"
+        | "variable_declaration"
+        | "ambient_declaration"
+        | "module"
+        | "internal_module"
+        | "method_definition"
+        | "method_signature"
+        | "abstract_method_signature"
+        | "public_field_definition"
+        | "index_signature" => Action::Item(node),
+        _ => Action::Drop,
+    }
+}
+
+fn visible_typescript(node: Node, src: &str, parent: Option<Node>) -> bool {
+    match parent {
+        // 类成员：排除 private 和 `#私有` 成员
+        Some(_) => {
+            let mut cursor = node.walk();
+            let private = node
+                .children(&mut cursor)
+                .any(|c| c.kind() == "accessibility_modifier" && &src[c.byte_range()] == "private");
+            !private && !name_of(node, src).starts_with('#')
+        }
+        None => node.kind() == "export_statement" || node.kind() == "ambient_declaration",
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Go
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn classify_go<'t>(node: Node<'t>, _nested: bool) -> Action<'t> {
+    match node.kind() {
+        "package_clause" | "import_declaration" => Action::Keep,
+        "function_declaration"
+        | "method_declaration"
+        | "type_declaration"
+        | "const_declaration"
+        | "var_declaration" => Action::Item(node),
+        _ => Action::Drop,
+    }
+}
+
+fn visible_go(node: Node, src: &str, _parent: Option<Node>) -> bool {
+    let exported = |name: &str| name.starts_with(|c: char| c.is_uppercase());
+
+    match node.kind() {
+        "function_declaration" | "method_declaration" => exported(name_of(node, src)),
+        // 分组声明中任一名称导出即保留
+        _ => {
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor).any(|spec| {
+                let mut spec_cursor = spec.walk();
+                spec.children_by_field_name("name", &mut spec_cursor)
+                    .any(|n| exported(&src[n.byte_range()]))
+            })
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Java
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn classify_java<'t>(node: Node<'t>, _nested: bool) -> Action<'t> {
+    match node.kind() {
+        "package_declaration" | "import_declaration" => Action::Keep,
+        "class_declaration" | "interface_declaration" | "record_declaration" => {
+            match node.child_by_field_name("body") {
+                Some(body) => Action::Container(node, body),
+                None => Action::Item(node),
+            }
+        }
+        "enum_declaration"
+        | "annotation_type_declaration"
+        | "method_declaration"
+        | "constructor_declaration"
+        | "field_declaration"
+        | "constant_declaration" => Action::Item(node),
+        _ => Action::Drop,
+    }
+}
+
+fn visible_java(node: Node, src: &str, parent: Option<Node>) -> bool {
+    // 接口成员默认公开
+    if parent.is_some_and(|p| p.kind() == "interface_declaration") {
+        return true;
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .filter(|c| c.kind() == "modifiers")
+        .any(|m| {
+            let text = &src[m.byte_range()];
+            text.split_whitespace()
+                .any(|word| word == "public" || word == "protected")
+        })
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 提示词：放在报告前后的说明和问题
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 内置的提示词片段；同名的用户片段优先
+const SNIPPETS: &[(&str, &str)] = &[
+    (
+        "review",
+        "你是一名资深工程师，正在审查下面的代码库。请关注正确性、可维护性、安全性和性能，\
+         按严重程度列出发现的问题，每条注明文件和行号，并给出修改建议。",
+    ),
+    (
+        "explain",
+        "请阅读下面的代码库，说明它的整体架构、主要模块的职责和相互调用关系，\
+         再沿一次典型的请求或命令执行过程解释关键的数据流。",
+    ),
+    (
+        "find-bugs",
+        "请仔细检查下面的代码库，找出可能的缺陷：逻辑错误、边界条件、遗漏的错误处理、\
+         并发问题和资源泄漏。每个问题注明文件、行号和触发条件，并给出修复方式。",
+    ),
+    (
+        "write-tests",
+        "请为下面的代码库编写测试，优先覆盖公共接口和边界条件，沿用项目已有的测试框架和目录约定。\
+         每个测试文件以 `### 路径` 标题加完整代码块的格式输出，便于用 apply 子命令写回。",
+    ),
+];
+
+/// 解析后的提示词
+pub struct Prompt {
+    pub text: String,
+    /// 来源，写入清单：`snippet:<名称>`、`file:<文件名>` 或 `text`
+    pub source: String,
+}
+
+/// 按顺序把参数解释为：已存在的文件、用户片段、内置片段，都不是时作为文本本身
+pub fn resolve(value: &str) -> Result<Prompt, String> {
+    let path = Path::new(value);
+    if path.is_file() {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("无法读取提示词文件 {}: {}", path.display(), e))?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        return Ok(Prompt {
+            text,
+            source: format!("file:{}", name),
+        });
+    }
+
+    if let Some(text) = user_snippet(value).or_else(|| builtin_snippet(value)) {
+        return Ok(Prompt {
+            text,
+            source: format!("snippet:{}", value),
+        });
+    }
+
+    if value.trim().is_empty() {
+        return Err("提示词为空".to_string());
+    }
+    Ok(Prompt {
+        text: value.to_string(),
+        source: "text".to_string(),
+    })
+}
+
+/// 用户片段目录：`$XDG_CONFIG_HOME/code-collector/prompts`，
+/// 未设置时依次使用 `%APPDATA%` 和 `~/.config`；片段文件为 `<名称>.md`
+fn snippet_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("code-collector").join("prompts"))
+}
+
+fn user_snippet(name: &str) -> Option<String> {
+    // 名称中不允许路径分隔符，避免读到片段目录之外的文件
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    fs::read_to_string(snippet_dir()?.join(format!("{}.md", name))).ok()
+}
+
+fn builtin_snippet(name: &str) -> Option<String> {
+    SNIPPETS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, text)| text.to_string())
+}
@@ -0,0 +1,255 @@
+use crate::FileEntry;
+use std::collections::{BTreeSet, HashMap};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 相关度排序与 token 预算
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// BM25 参数
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// 路径中的词比内容中的词更能说明文件的用途，按这个倍数计入词频
+const PATH_WEIGHT: usize = 3;
+
+/// 直接导入或被导入、隔一层导入的文件分得的相关度比例
+const NEIGHBOR_WEIGHT: f64 = 0.5;
+const SECOND_NEIGHBOR_WEIGHT: f64 = 0.25;
+
+/// 修改时间的权重：最新的文件加满这个值，最旧的不加
+const RECENCY_WEIGHT: f64 = 0.1;
+
+/// 按查询给每个文件打分：BM25（标识符和路径）为主，导入关系和修改时间为辅
+///
+/// `contents` 与 `entries` 一一对应，`edges` 为每个文件导入的文件下标；
+/// 分数保留三位小数，最高分不超过 `1 + NEIGHBOR_WEIGHT + RECENCY_WEIGHT`
+pub fn score(
+    query: &str,
+    entries: &[FileEntry],
+    contents: &[String],
+    edges: &[Vec<usize>],
+) -> Vec<f64> {
+    // 有序集合：浮点求和的顺序固定，分数在多次运行间一致
+    let query: BTreeSet<String> = terms(query).collect();
+    let documents: Vec<HashMap<String, usize>> = entries
+        .iter()
+        .zip(contents)
+        .map(|(entry, content)| {
+            let mut frequencies: HashMap<String, usize> = HashMap::new();
+            for term in terms(content) {
+                *frequencies.entry(term).or_default() += 1;
+            }
+            for term in terms(&entry.relative_path) {
+                *frequencies.entry(term).or_default() += PATH_WEIGHT;
+            }
+            frequencies
+        })
+        .collect();
+
+    let relevance = normalize(&bm25(&query, &documents));
+    let proximity = proximity(&relevance, edges);
+    let recency = recency(entries);
+
+    relevance
+        .iter()
+        .zip(&proximity)
+        .zip(&recency)
+        .map(|((r, p), t)| ((r + p + RECENCY_WEIGHT * t) * 1000.0).round() / 1000.0)
+        .collect()
+}
+
+fn bm25(query: &BTreeSet<String>, documents: &[HashMap<String, usize>]) -> Vec<f64> {
+    let count = documents.len() as f64;
+    let lengths: Vec<f64> = documents
+        .iter()
+        .map(|d| d.values().sum::<usize>() as f64)
+        .collect();
+    let average = (lengths.iter().sum::<f64>() / count.max(1.0)).max(1.0);
+
+    let idf: Vec<(&String, f64)> = query
+        .iter()
+        .map(|term| {
+            let df = documents.iter().filter(|d| d.contains_key(term)).count() as f64;
+            (term, ((count - df + 0.5) / (df + 0.5) + 1.0).ln())
+        })
+        .collect();
+
+    documents
+        .iter()
+        .zip(&lengths)
+        .map(|(document, length)| {
+            idf.iter()
+                .filter_map(|(term, idf)| {
+                    let tf = *document.get(*term)? as f64;
+                    Some(idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / average)))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// 缩放到 0..=1，最相关的文件为 1
+fn normalize(scores: &[f64]) -> Vec<f64> {
+    let max = scores.iter().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return vec![0.0; scores.len()];
+    }
+    scores.iter().map(|s| s / max).collect()
+}
+
+/// 相关文件的邻居（导入或被导入）也可能需要一起阅读
+fn proximity(relevance: &[f64], edges: &[Vec<usize>]) -> Vec<f64> {
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); relevance.len()];
+    for (from, targets) in edges.iter().enumerate() {
+        for &to in targets {
+            neighbors[from].push(to);
+            neighbors[to].push(from);
+        }
+    }
+
+    let nearest = |values: &[f64]| -> Vec<f64> {
+        neighbors
+            .iter()
+            .map(|list| list.iter().map(|&j| values[j]).fold(0.0, f64::max))
+            .collect()
+    };
+    let first = nearest(relevance);
+    let second = nearest(&first);
+    first
+        .iter()
+        .zip(&second)
+        .map(|(a, b)| (NEIGHBOR_WEIGHT * a).max(SECOND_NEIGHBOR_WEIGHT * b))
+        .collect()
+}
+
+/// 按修改时间缩放到 0..=1，最新的为 1；时间相同或未知时为 0
+fn recency(entries: &[FileEntry]) -> Vec<f64> {
+    let times: Vec<Option<f64>> = entries
+        .iter()
+        .map(|e| {
+            let modified = e.modified?.duration_since(std::time::UNIX_EPOCH).ok()?;
+            Some(modified.as_secs_f64())
+        })
+        .collect();
+    let known = times.iter().flatten();
+    let oldest = known.clone().copied().fold(f64::INFINITY, f64::min);
+    let newest = known.copied().fold(f64::NEG_INFINITY, f64::max);
+    times
+        .iter()
+        .map(|t| match t {
+            Some(t) if newest > oldest => (t - oldest) / (newest - oldest),
+            _ => 0.0,
+        })
+        .collect()
+}
+
+/// 拆分标识符：按非字母数字字符、驼峰边界切分，转小写，去掉过短的词和复数 `s`
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .flat_map(split_camel_case)
+        .filter(|word| word.chars().count() >= 2)
+        .map(|word| {
+            let word = word.to_lowercase();
+            match word.strip_suffix('s') {
+                Some(stem) if stem.len() >= 3 && !stem.ends_with('s') => stem.to_string(),
+                _ => word,
+            }
+        })
+}
+
+/// `refreshToken` → `refresh`、`Token`；`HTTPServer` → `HTTP`、`Server`
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 1..chars.len() {
+        let (at, c) = chars[i];
+        let previous = chars[i - 1].1;
+        let next_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+        let boundary = c.is_uppercase()
+            && (previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || previous.is_uppercase() && next_lower);
+        if boundary {
+            parts.push(&word[start..at]);
+            start = at;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+/// 按 `priority` 的先后贪心装入预算，放不下的跳过、继续尝试后面较小的文件
+///
+/// `costs` 为每个文件占用的 token 数；返回未装入的文件下标（按 `priority` 的顺序）
+pub fn pack(costs: &[usize], priority: &[usize], budget: usize) -> Vec<usize> {
+    let mut used = 0;
+    let mut excluded = Vec::new();
+    for &i in priority {
+        if used + costs[i] <= budget {
+            used += costs[i];
+        } else {
+            excluded.push(i);
+        }
+    }
+    excluded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loc::LineCounts;
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry {
+            relative_path: path.to_string(),
+            extension: "rs".to_string(),
+            language: "rust".to_string(),
+            size: 0,
+            line_count: 0,
+            lines: LineCounts::default(),
+            tokens: 0,
+            hash: String::new(),
+            encoding: None,
+            truncated: false,
+            modified: None,
+            score: None,
+            tree_index: 0,
+        }
+    }
+
+    #[test]
+    fn identifiers_split_into_lowercase_terms() {
+        assert_eq!(split_camel_case("refreshToken"), ["refresh", "Token"]);
+        assert_eq!(split_camel_case("HTTPServer"), ["HTTP", "Server"]);
+        let words: Vec<String> = terms("refreshToken active_users a").collect();
+        assert_eq!(words, ["refresh", "token", "active", "user"]);
+    }
+
+    #[test]
+    fn matching_files_and_their_importers_rank_higher() {
+        let entries = [
+            entry("src/auth/token.rs"),
+            entry("src/auth/mod.rs"),
+            entry("src/ui/canvas.rs"),
+        ];
+        let contents = [
+            "pub fn refresh_token(token: &str) {}".to_string(),
+            "mod token;".to_string(),
+            "pub fn draw() {}".to_string(),
+        ];
+        let edges = [vec![], vec![0], vec![]];
+        let scores = score("refresh token", &entries, &contents, &edges);
+        assert!(scores[0] > scores[1]);
+        assert!(scores[1] > scores[2]);
+        assert_eq!(scores[2], 0.0);
+    }
+
+    #[test]
+    fn pack_skips_large_file_and_fits_smaller_one() {
+        let costs = [100, 500, 50];
+        assert_eq!(pack(&costs, &[0, 1, 2], 200), [1]);
+        assert_eq!(pack(&costs, &[1, 0, 2], 200), [1]);
+        assert_eq!(pack(&costs, &[0, 1, 2], 120), [1, 2]);
+    }
+}
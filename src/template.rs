@@ -0,0 +1,173 @@
+use crate::manifest::config_json;
+use crate::normalize::LineEndings;
+use crate::{Config, RenderedFile, Stats, VERSION, format_size, source_display, source_name};
+use chrono::{DateTime, FixedOffset};
+use minijinja::{AutoEscape, Environment};
+use serde_json::{Value, json};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 报告模板
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 内置模板，即默认的报告布局
+pub const DEFAULT: &str = include_str!("../templates/default.md.jinja");
+
+/// 已编译的报告模板
+///
+/// 开启 `trim_blocks` 和 `lstrip_blocks`：独占一行的 `{% %}` 标签不会留下空行；不做 HTML 转义
+pub struct Template {
+    env: Environment<'static>,
+    name: String,
+}
+
+impl Template {
+    /// 编译模板，语法错误在扫描文件之前报告；`name` 用于错误信息中的定位
+    pub fn compile(name: &str, source: String) -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_keep_trailing_newline(true);
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        env.add_filter("filesize", format_size);
+        env.add_template_owned(name.to_string(), source)?;
+        Ok(Self {
+            env,
+            name: name.to_string(),
+        })
+    }
+
+    pub fn render(&self, context: &Value) -> Result<String, minijinja::Error> {
+        self.env.get_template(&self.name)?.render(context)
+    }
+}
+
+/// 模板上下文：项目信息、生效配置、统计、目录树、依赖图和每个文件的内容
+pub fn context(
+    config: &Config,
+    generated: Option<DateTime<FixedOffset>>,
+    stats: &Stats,
+    tree: &[String],
+    graph: Option<String>,
+    files: &[RenderedFile],
+) -> Value {
+    let project = if config.reproducible {
+        source_name(&config.path)
+    } else {
+        config
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Project".to_string())
+    };
+
+    json!({
+        "project": project,
+        "version": VERSION,
+        "generated": generated.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()),
+        "source": source_display(config),
+        "config": config_json(config),
+        "stats": stats_json(stats),
+        "tree": tree,
+        "graph": graph,
+        "entries": files.iter().map(file_json).collect::<Vec<_>>(),
+    })
+}
+
+fn file_json(file: &RenderedFile) -> Value {
+    let entry = file.entry;
+    let mut content = file.content.clone();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    json!({
+        "path": entry.relative_path,
+        "anchor": entry.relative_path.replace(['/', '.', ' '], "-").to_lowercase(),
+        "extension": entry.extension,
+        "language": entry.language,
+        "size": entry.size,
+        "lines": entry.line_count,
+        "code": entry.lines.code,
+        "comment": entry.lines.comment,
+        "blank": entry.lines.blank,
+        "tokens": entry.tokens,
+        "hash": entry.hash,
+        "encoding": entry.encoding,
+        "short_hash": &entry.hash[..crate::bundle::HASH_PREFIX],
+        "line_endings": file.line_endings.map(LineEndings::as_str),
+        "normalized": file.normalized,
+        "omitted": file.omitted,
+        "score": entry.score,
+        "outlined": file.outlined,
+        "trimmed": file.trimmed,
+        "redactions": file.redactions,
+        "fence": file.fence(),
+        "content": content,
+    })
+}
+
+fn stats_json(stats: &Stats) -> Value {
+    let mut file_types: Vec<_> = stats.file_types.iter().collect();
+    file_types.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let mut languages: Vec<_> = stats.languages.iter().collect();
+    languages.sort_by(|a, b| b.1.lines.code.cmp(&a.1.lines.code).then(a.0.cmp(b.0)));
+
+    json!({
+        "files": stats.files_processed,
+        "dirs": stats.dirs_count,
+        "lines": stats.total_lines,
+        "code": stats.lines.code,
+        "comment": stats.lines.comment,
+        "blank": stats.lines.blank,
+        "bytes": stats.total_bytes,
+        "tokens": stats.total_tokens,
+        "prompt_tokens": stats.prompt_tokens,
+        "skipped": {
+            "too_large": stats.files_skipped_size,
+            "binary": stats.files_skipped_binary,
+            "encoding": stats.files_skipped_encoding,
+            "sensitive": stats.files_withheld,
+            "filtered": stats.files_skipped_filter,
+            "generated": stats.files_skipped_generated,
+            "minified": stats.files_skipped_minified,
+            "vendored": stats.files_skipped_vendored,
+        },
+        "transcoded": stats.files_transcoded,
+        "normalized": stats.files_normalized,
+        "excluded": stats
+            .excluded
+            .iter()
+            .map(|(path, tokens, score)| json!({ "path": path, "tokens": tokens, "score": score }))
+            .collect::<Vec<_>>(),
+        "truncated": stats
+            .truncated
+            .iter()
+            .map(|(path, omitted)| json!({ "path": path, "omitted": omitted }))
+            .collect::<Vec<_>>(),
+        "outlined": stats.files_outlined,
+        "bytes_saved": stats.bytes_saved,
+        "tokens_saved": stats.tokens_saved,
+        "file_types": file_types
+            .into_iter()
+            .map(|(extension, files)| json!({ "extension": extension, "files": files }))
+            .collect::<Vec<_>>(),
+        "languages": languages
+            .into_iter()
+            .map(|(name, language)| json!({
+                "name": name,
+                "files": language.files,
+                "code": language.lines.code,
+                "comment": language.lines.comment,
+                "blank": language.lines.blank,
+                "total": language.lines.total(),
+            }))
+            .collect::<Vec<_>>(),
+        "secrets": stats
+            .secret_findings
+            .iter()
+            .map(|(path, finding)| json!({ "path": path, "line": finding.line, "rule": finding.rule }))
+            .collect::<Vec<_>>(),
+        "sensitive_included": stats.sensitive_included,
+    })
+}
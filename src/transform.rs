@@ -0,0 +1,172 @@
+use crate::loc::{self, Comment};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 内容转换选项
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 写入前对文件内容做的精简处理，用于压缩 prompt 体积
+#[derive(Clone, Default)]
+pub struct TransformOptions {
+    pub strip_comments: bool,
+    pub keep_doc_comments: bool,
+    pub collapse_blank_lines: bool,
+    pub trim_trailing: bool,
+    pub strip_license: bool,
+}
+
+impl TransformOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.strip_comments || self.collapse_blank_lines || self.trim_trailing || self.strip_license
+    }
+}
+
+// 许可证头部的关键字（小写匹配）
+const LICENSE_MARKERS: &[&str] = &["license", "copyright", "spdx-license-identifier"];
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 转换实现
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 按语言对内容应用转换，返回处理后的文本
+pub fn apply(content: &str, lang: &str, options: &TransformOptions) -> String {
+    let comments = loc::syntax_for(lang)
+        .map(|syntax| loc::scan_comments(content, syntax))
+        .unwrap_or_default();
+
+    let mut removed: Vec<Comment> = Vec::new();
+    if options.strip_license {
+        removed.extend(license_header(content, &comments));
+    }
+    if options.strip_comments {
+        removed.extend(
+            comments
+                .iter()
+                .filter(|c| !(is_shebang(content, c) || options.keep_doc_comments && c.doc)),
+        );
+    }
+    removed.sort_by_key(|c| c.start);
+    removed.dedup_by_key(|c| c.start);
+
+    let mut output = String::with_capacity(content.len());
+    let mut next_removed = 0;
+    let mut line_start = 0;
+    let mut previous_blank = false;
+
+    for raw_line in content.split_inclusive('\n') {
+        let start = line_start;
+        line_start += raw_line.len();
+
+        let (line, ending) = split_line_ending(raw_line);
+        let end = start + line.len();
+
+        // 去掉与本行相交的注释片段
+        let mut kept = String::new();
+        let mut cursor = start;
+        let mut touched = false;
+        while next_removed < removed.len() && removed[next_removed].end <= start {
+            next_removed += 1;
+        }
+        let mut i = next_removed;
+        while i < removed.len() && removed[i].start < end {
+            let comment = removed[i];
+            let cut_start = comment.start.max(start);
+            let cut_end = comment.end.min(end);
+            if cut_start > cursor {
+                kept.push_str(&content[cursor..cut_start]);
+            }
+            cursor = cursor.max(cut_end);
+            touched = true;
+            i += 1;
+        }
+        if cursor < end {
+            kept.push_str(&content[cursor..end]);
+        }
+
+        // 被注释独占的行整行删除
+        if touched && kept.trim().is_empty() {
+            continue;
+        }
+
+        // 保留原缩进，去掉注释两侧残留的空白
+        let mut line = if touched {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            format!("{}{}", indent, kept.trim())
+        } else {
+            kept
+        };
+        if options.trim_trailing {
+            line.truncate(line.trim_end().len());
+        }
+
+        let blank = line.trim().is_empty();
+        if options.collapse_blank_lines && blank && previous_blank {
+            continue;
+        }
+        previous_blank = blank;
+
+        output.push_str(&line);
+        output.push_str(ending);
+    }
+
+    if options.strip_license {
+        // 许可证头部删除后，去掉开头残留的空行
+        let leading = output.len() - output.trim_start_matches(['\r', '\n']).len();
+        output.drain(..leading);
+    }
+
+    output
+}
+
+fn split_line_ending(raw_line: &str) -> (&str, &str) {
+    if let Some(line) = raw_line.strip_suffix("\r\n") {
+        (line, "\r\n")
+    } else if let Some(line) = raw_line.strip_suffix('\n') {
+        (line, "\n")
+    } else {
+        (raw_line, "")
+    }
+}
+
+fn is_shebang(content: &str, comment: &Comment) -> bool {
+    comment.start == 0 && content.starts_with("#!")
+}
+
+/// 文件开头（可跳过 shebang 和空白）连续的注释块，若包含许可证关键字则返回
+fn license_header(content: &str, comments: &[Comment]) -> Vec<Comment> {
+    let mut header = Vec::new();
+    let mut cursor = 0;
+
+    for comment in comments {
+        let gap = &content[cursor..comment.start];
+        // 遇到代码或空行即视为头部结束
+        if !gap.trim().is_empty() || (!header.is_empty() && gap.matches('\n').count() > 1) {
+            break;
+        }
+        cursor = comment.end;
+        if !is_shebang(content, comment) {
+            header.push(*comment);
+        }
+    }
+
+    let is_license = header.iter().any(|c| {
+        let text = content[c.start..c.end].to_lowercase();
+        LICENSE_MARKERS.iter().any(|marker| text.contains(marker))
+    });
+
+    if is_license { header } else { Vec::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_comments_keeps_shell_parameter_expansions() {
+        let options = TransformOptions {
+            strip_comments: true,
+            ..Default::default()
+        };
+        let content = "echo ${#arr[@]} $# a#b  # count\n";
+        assert_eq!(apply(content, "bash", &options), "echo ${#arr[@]} $# a#b\n");
+    }
+}
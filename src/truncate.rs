@@ -0,0 +1,57 @@
+// ═══════════════════════════════════════════════════════════════════════════
+// 大文件截断：保留开头和结尾
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 截断后的内容
+pub struct Excerpt {
+    pub text: String,
+    /// 省略的行数
+    pub omitted: usize,
+}
+
+/// 省略标记所在的行
+pub fn marker(omitted: usize) -> String {
+    format!("… 已省略 {} 行 …", omitted)
+}
+
+/// 保留前 `head` 行和后 `tail` 行，中间换成省略标记；行数不超过两者之和时返回 `None`
+pub fn excerpt(content: &str, head: usize, tail: usize) -> Option<Excerpt> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    if lines.len() <= head + tail {
+        return None;
+    }
+    let omitted = lines.len() - head - tail;
+
+    let mut text: String = lines[..head].concat();
+    text.push_str(&marker(omitted));
+    text.push('\n');
+    text.push_str(&lines[lines.len() - tail..].concat());
+    Some(Excerpt { text, omitted })
+}
+
+/// 截断过大的文件：保留的部分仍超过 `max_bytes`（如只有几行超长的行）时返回 `None`，按过大跳过
+pub fn fit(content: &str, head: usize, tail: usize, max_bytes: u64) -> Option<Excerpt> {
+    excerpt(content, head, tail).filter(|excerpt| excerpt.text.len() as u64 <= max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_head_and_tail_lines() {
+        let content: String = (1..=10).map(|i| format!("{}\n", i)).collect();
+        let excerpt = fit(&content, 2, 1, 1024).unwrap();
+        assert_eq!(excerpt.text, format!("1\n2\n{}\n10\n", marker(7)));
+        assert_eq!(excerpt.omitted, 7);
+    }
+
+    #[test]
+    fn few_long_lines_do_not_fit() {
+        let line = format!("{}\n", "x".repeat(4096));
+        // 行数不超过首尾之和，无法按行截断
+        assert!(fit(&line.repeat(3), 200, 50, 1024).is_none());
+        // 能按行截断，但保留的行仍超过上限
+        assert!(fit(&line.repeat(20), 5, 5, 1024).is_none());
+    }
+}
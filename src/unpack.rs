@@ -0,0 +1,179 @@
+use crate::bundle::{self, BundleFile};
+use crate::print_colored;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// unpack 子命令：把 Markdown 包还原为目录
+// ═══════════════════════════════════════════════════════════════════════════
+
+struct Options {
+    bundle: PathBuf,
+    target: PathBuf,
+    dry_run: bool,
+    force: bool,
+}
+
+enum Action {
+    Create,
+    Unchanged,
+    Overwrite,
+    Rejected(String),
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(options) = parse_args(args)? else {
+        return Ok(());
+    };
+
+    let text = fs::read_to_string(&options.bundle)
+        .map_err(|e| format!("无法读取 {}: {}", options.bundle.display(), e))?;
+    let files = bundle::parse(&text, false).files;
+    if files.is_empty() {
+        return Err(format!("未在 {} 中找到文件段落", options.bundle.display()).into());
+    }
+
+    let plan = plan(&options, &files);
+    let mut conflicts = 0;
+    let mut rejected = 0;
+    for (file, _, action) in &plan {
+        match action {
+            Action::Create => print_colored("green", "+", &file.path),
+            Action::Unchanged => print_colored("gray", "=", &format!("{}（内容相同）", file.path)),
+            Action::Overwrite if options.force => {
+                print_colored("yellow", "~", &format!("{}（覆盖）", file.path))
+            }
+            Action::Overwrite => {
+                conflicts += 1;
+                print_colored("red", "!", &format!("{}（已存在且内容不同）", file.path));
+            }
+            Action::Rejected(reason) => {
+                rejected += 1;
+                print_colored("red", "✗", reason);
+            }
+        }
+        if file.altered && !matches!(action, Action::Rejected(_)) {
+            print_colored("yellow", "  ⚠", "该文件为大纲或已脱敏内容，与原文件不同");
+        }
+    }
+
+    if rejected > 0 {
+        return Err(format!("{} 个路径不安全，已中止", rejected).into());
+    }
+    if conflicts > 0 && !options.dry_run {
+        return Err(format!("{} 个文件冲突，使用 -Force 覆盖", conflicts).into());
+    }
+    if options.dry_run {
+        println!();
+        print_colored(
+            "blue",
+            "→",
+            &format!("预演完成，共 {} 个文件，未写入任何内容", plan.len()),
+        );
+        return Ok(());
+    }
+
+    let mut written = 0;
+    for (file, target, action) in &plan {
+        if matches!(action, Action::Create | Action::Overwrite) {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(target, &file.content)?;
+            written += 1;
+        }
+    }
+
+    println!();
+    print_colored(
+        "green",
+        "✓",
+        &format!(
+            "已还原 {} 个文件到 {}（{} 个未变化）",
+            written,
+            options.target.display(),
+            plan.len() - written
+        ),
+    );
+    Ok(())
+}
+
+/// 逐个文件判断写入动作；同一路径在包中重复出现时只保留第一次
+fn plan<'a>(options: &Options, files: &'a [BundleFile]) -> Vec<(&'a BundleFile, PathBuf, Action)> {
+    let mut seen = HashSet::new();
+    files
+        .iter()
+        .map(|file| {
+            let (target, action) = match bundle::safe_join(&options.target, &file.path) {
+                Err(reason) => (PathBuf::new(), Action::Rejected(reason)),
+                Ok(target) if !seen.insert(target.clone()) => {
+                    (target, Action::Rejected(format!("路径重复: {}", file.path)))
+                }
+                Ok(target) => {
+                    let action = match fs::read(&target) {
+                        Err(_) if target.is_dir() => {
+                            Action::Rejected(format!("目标是目录: {}", file.path))
+                        }
+                        Err(_) => Action::Create,
+                        Ok(existing) if existing == file.content.as_bytes() => Action::Unchanged,
+                        Ok(_) => Action::Overwrite,
+                    };
+                    (target, action)
+                }
+            };
+            (file, target, action)
+        })
+        .collect()
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, Box<dyn std::error::Error>> {
+    let mut bundle = None;
+    let mut options = Options {
+        bundle: PathBuf::new(),
+        target: PathBuf::from("."),
+        dry_run: false,
+        force: false,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-h" | "--help" => {
+                print_help();
+                return Ok(None);
+            }
+            "-Path" => {
+                i += 1;
+                options.target = PathBuf::from(args.get(i).ok_or("缺少 -Path 的值")?);
+            }
+            "-DryRun" => options.dry_run = true,
+            "-Force" => options.force = true,
+            arg if !arg.starts_with('-') && bundle.is_none() => bundle = Some(PathBuf::from(arg)),
+            arg => return Err(format!("未知参数: {}", arg).into()),
+        }
+        i += 1;
+    }
+
+    options.bundle = bundle.ok_or("缺少要还原的 Markdown 文件，用法见 unpack -h")?;
+    Ok(Some(options))
+}
+
+fn print_help() {
+    println!(
+        r#"
+用法:
+  code_collector unpack <Markdown 文件> [选项]
+
+把 code_collector 生成的 Markdown 还原为目录结构。
+
+选项:
+  -Path <目录>         还原到的目录（默认: 当前目录）
+  -DryRun              只列出将要写入的文件，不写入
+  -Force               覆盖已存在且内容不同的文件
+  -h, --help           显示帮助信息
+
+路径包含 `..` 或为绝对路径时拒绝还原；大纲或已脱敏的文件会给出提示。
+"#
+    );
+}
@@ -0,0 +1,63 @@
+use crate::print_colored;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+// ═══════════════════════════════════════════════════════════════════════════
+// 监视模式
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// 最后一个事件之后静默多久才触发重新生成
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 监视 `root` 下的文件变化，事件静默 `DEBOUNCE` 后调用 `rebuild`；
+/// `rebuild` 返回 `false` 时停止监视，否则只在出错时返回
+pub fn watch(
+    root: &Path,
+    is_ignored: impl Fn(&Path) -> bool,
+    mut rebuild: impl FnMut() -> bool,
+) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+
+    let relevant = |event: notify::Result<Event>| -> bool {
+        // 监视出错（如事件队列溢出）时可能漏掉变化，提示后按有变化处理
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                print_colored("yellow", "⚠", &format!("监视出错: {}，将重新生成", e));
+                return true;
+            }
+        };
+        // 读取文件本身也会产生访问事件，只关心写入完成
+        let changes_content = match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+            EventKind::Access(_) => false,
+            _ => true,
+        };
+        changes_content && event.paths.iter().any(|p| !is_ignored(p))
+    };
+
+    loop {
+        let Ok(event) = rx.recv() else {
+            return Ok(());
+        };
+        let mut dirty = relevant(event);
+
+        // 合并短时间内的连续事件
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(event) => dirty |= relevant(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        if dirty && !rebuild() {
+            return Ok(());
+        }
+    }
+}